@@ -5,8 +5,11 @@
 #![warn(rustdoc)]
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{Data, DeriveInput, Expr, Ident, Lit, Meta, Type, TypePath};
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Data, DeriveInput, Expr, Fields, GenericArgument, Ident, Lit, Meta, MetaList,
+    NestedMeta, PathArguments, Type, TypePath, UnOp,
+};
 
 struct EntityGetter {
     // Property name in the datastore entity
@@ -26,19 +29,46 @@ struct FieldMeta {
     from_property: Expr,
     // Data used to build datastore getters
     entity_getter: Option<EntityGetter>,
+    // Data used to build the typed property descriptor
+    property_descriptor: PropertyDescriptor,
+}
+
+struct PropertyDescriptor {
+    // Name of the associated constant holding the descriptor
+    const_ident: Ident,
+    // Property name in the datastore entity
+    datastore_property: String,
+    // Type of the individual property values
+    value_type: Type,
 }
 
 fn build_field_meta(
     ident: Ident,
+    field_type: &Type,
     datastore_property_name: &str,
     struct_property_name: &str,
     indexed: bool,
+    unindexed: bool,
+    truncate_millis: bool,
 ) -> FieldMeta {
-    let into_property_expr_string = format!("properties.get(\"{}\")", datastore_property_name);
-    let from_property_expr_string = format!(
-        "properties.set(\"{}\", entity.{})",
-        datastore_property_name, struct_property_name
+    let into_property_expr_string = format!(
+        "properties.get_property(<Self as datastorers::Kind>::kind_str(), &entity_key, \"{}\")",
+        datastore_property_name
     );
+    let set_method = match unindexed {
+        true => "set_unindexed",
+        false => "set",
+    };
+    let from_property_expr_string = match truncate_millis {
+        true => format!(
+            "properties.{}(\"{}\", datastorers::serialize::TruncateMillis::truncate_millis(entity.{}))",
+            set_method, datastore_property_name, struct_property_name
+        ),
+        false => format!(
+            "properties.{}(\"{}\", entity.{})",
+            set_method, datastore_property_name, struct_property_name
+        ),
+    };
     let entity_getter = match indexed {
         true => Some(EntityGetter {
             get_one_method_name: parse_expr(&format!("get_one_by_{}", struct_property_name)),
@@ -52,9 +82,89 @@ fn build_field_meta(
         into_property: parse_expr(&into_property_expr_string),
         from_property: parse_expr(&from_property_expr_string),
         entity_getter,
+        property_descriptor: build_property_descriptor(
+            field_type,
+            datastore_property_name,
+            struct_property_name,
+        ),
+    }
+}
+
+fn build_property_descriptor(
+    field_type: &Type,
+    datastore_property_name: &str,
+    struct_property_name: &str,
+) -> PropertyDescriptor {
+    PropertyDescriptor {
+        const_ident: format_ident!(
+            "{}",
+            struct_property_name.trim_start_matches("r#").to_uppercase()
+        ),
+        datastore_property: datastore_property_name.to_string(),
+        value_type: property_value_type(field_type),
     }
 }
 
+/// The type of the individual values of a property, which is the field type with
+/// any `Option` and `Vec` wrappers removed.
+fn property_value_type(field_type: &Type) -> Type {
+    if let Type::Path(type_path) = field_type {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == "Option" || segment.ident == "Vec" {
+                if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                    if arguments.args.len() == 1 {
+                        if let Some(GenericArgument::Type(inner)) = arguments.args.first() {
+                            return property_value_type(inner);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    field_type.clone()
+}
+
+struct IndexDeclaration {
+    ancestor: bool,
+    // Struct field names and directions of the indexed properties
+    properties: Vec<(String, Ident)>,
+}
+
+fn parse_index_declaration(list: &MetaList) -> IndexDeclaration {
+    let mut declaration = IndexDeclaration {
+        ancestor: false,
+        properties: Vec::new(),
+    };
+    for nested in list.nested.iter() {
+        match nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("ancestor") => {
+                declaration.ancestor = true;
+            }
+            NestedMeta::Meta(Meta::List(properties)) if properties.path.is_ident("properties") => {
+                for property in properties.nested.iter() {
+                    match property {
+                        NestedMeta::Meta(Meta::NameValue(name_value)) => {
+                            let direction = match &name_value.lit {
+                                Lit::Str(lit_str) => match lit_str.value().as_str() {
+                                    "asc" => format_ident!("Ascending"),
+                                    "desc" => format_ident!("Descending"),
+                                    _ => panic!("index direction must be \"asc\" or \"desc\""),
+                                },
+                                _ => panic!("invalid value type for index direction"),
+                            };
+                            let field = name_value.path.get_ident().unwrap().to_string();
+                            declaration.properties.push((field, direction));
+                        }
+                        _ => panic!("index properties must be declared as field = \"asc\" or field = \"desc\""),
+                    }
+                }
+            }
+            _ => panic!("invalid index attribute, expected ancestor or properties(...)"),
+        }
+    }
+    declaration
+}
+
 struct KeyProperty {
     name: String,
     tp: TypePath,
@@ -62,15 +172,28 @@ struct KeyProperty {
 
 #[proc_macro_derive(
     DatastoreManaged,
-    attributes(kind, key, indexed, property, page_size, version)
+    attributes(
+        kind,
+        key,
+        indexed,
+        property,
+        page_size,
+        version,
+        index,
+        truncate_millis,
+        unindexed,
+        extra
+    )
 )]
 pub fn datastore_managed(input: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
 
     let mut kind: Option<String> = None;
     let mut version_field: Option<String> = None;
+    let mut extra_field: Option<String> = None;
     let mut key_field: Option<KeyProperty> = None;
     let mut page_size: Expr = parse_expr("None");
+    let mut index_declarations: Vec<IndexDeclaration> = Vec::new();
 
     let fields: Vec<FieldMeta> = match ast.data {
         Data::Struct(vdata) => {
@@ -93,12 +216,17 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
                             _ => (),
                         }
                     }
+                    Meta::List(ref list) if list.path.is_ident("index") => {
+                        index_declarations.push(parse_index_declaration(list));
+                    }
                     _ => (),
                 }
             }
 
             for ref field in vdata.fields.iter() {
                 let mut indexed: bool = false;
+                let mut unindexed: bool = false;
+                let mut truncate_millis: bool = false;
                 let mut property_name: Option<String> = None;
 
                 for ref attr in &field.attrs {
@@ -117,12 +245,31 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
                                     version_field =
                                         Some(field.ident.as_ref().unwrap().clone().to_string());
                                 }
+                                "extra" => {
+                                    if extra_field.is_some() {
+                                        panic!("only one field can be marked as extra");
+                                    }
+                                    extra_field =
+                                        Some(field.ident.as_ref().unwrap().clone().to_string());
+                                }
                                 "indexed" => {
                                     indexed = true;
                                 }
+                                "unindexed" => {
+                                    unindexed = true;
+                                }
+                                "truncate_millis" => {
+                                    truncate_millis = true;
+                                }
                                 _ => (),
                             }
                         }
+                        Meta::List(ref list) if list.path.is_ident("indexed") => {
+                            match indexed_value(list) {
+                                true => indexed = true,
+                                false => unindexed = true,
+                            }
+                        }
                         Meta::NameValue(ref name_value) => {
                             match name_value.path.get_ident().unwrap().to_string().as_str() {
                                 "property" => match &name_value.lit {
@@ -159,14 +306,27 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
                             }
                             None => (),
                         }
+                        // Ignore extra field if set
+                        if extra_field.as_ref() == Some(&struct_property_name) {
+                            continue;
+                        }
+                        if indexed && unindexed {
+                            panic!(
+                                "{} can not be both indexed and unindexed",
+                                struct_property_name
+                            );
+                        }
                         let datastore_property_name =
                             property_name.unwrap_or_else(|| struct_property_name.clone());
 
                         field_metas.push(build_field_meta(
                             ident,
+                            &field.ty,
                             &datastore_property_name,
                             &struct_property_name,
                             indexed,
+                            unindexed,
+                            truncate_millis,
                         ));
                     }
                     _ => (), // Ignore
@@ -203,6 +363,15 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
         entity_version = parse_expr(&format!("entity.{}", &version));
     }
 
+    // Properties that are not mapped to a field are kept in the extra field, if set,
+    // and written back when the entity is stored
+    let mut extra_field_assignements = vec![];
+    let mut entity_properties = parse_expr("datastorers::DatastoreProperties::new()");
+    if let Some(extra) = extra_field {
+        extra_field_assignements.push(parse_expr(&format!("{}: properties", &extra)));
+        entity_properties = parse_expr(&format!("entity.{}", &extra));
+    }
+
     let entity_getters = fields
         .iter()
         .filter(|f| f.entity_getter.is_some())
@@ -225,6 +394,47 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
         .map(|f| f.entity_getter.as_ref().unwrap().datastore_property.clone())
         .collect::<Vec<_>>();
 
+    let property_consts = fields
+        .iter()
+        .map(|f| f.property_descriptor.const_ident.clone())
+        .collect::<Vec<_>>();
+    let property_value_types = fields
+        .iter()
+        .map(|f| f.property_descriptor.value_type.clone())
+        .collect::<Vec<_>>();
+    let property_names = fields
+        .iter()
+        .map(|f| f.property_descriptor.datastore_property.clone())
+        .collect::<Vec<_>>();
+
+    let indexes = index_declarations
+        .iter()
+        .map(|index| {
+            let ancestor = index.ancestor;
+            let (index_properties, directions): (Vec<_>, Vec<_>) = index
+                .properties
+                .iter()
+                .map(|(field, direction)| {
+                    let property = fields
+                        .iter()
+                        .find(|f| &f.ident.to_string() == field)
+                        .unwrap_or_else(|| panic!("index references unknown field {}", field));
+                    (
+                        property.property_descriptor.datastore_property.clone(),
+                        direction.clone(),
+                    )
+                })
+                .unzip();
+            quote! {
+                datastorers::index::CompositeIndex::new(
+                    #kind_str,
+                    #ancestor,
+                    vec![#((#index_properties, datastorers::Order::#directions)),*],
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+
     let tokens = quote! {
         impl datastorers::Kind for #name {
             fn kind(&self) -> &'static str {
@@ -236,17 +446,42 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
             }
         }
 
+        impl datastorers::Keyed for #name {
+            type Key = #key_field_type;
+        }
+
         impl datastorers::Pagable for #name {
             fn page_size() -> Option<i32> {
                 #page_size
             }
         }
 
+        impl datastorers::index::DatastoreIndexes for #name {
+            fn indexes() -> Vec<datastorers::index::CompositeIndex> {
+                vec![#(#indexes),*]
+            }
+        }
+
         impl #name {
+            #(
+                pub const #property_consts: datastorers::Property<#name, #property_value_types> =
+                    datastorers::Property::new(#property_names);
+            )*
+
             pub fn id(&self) -> &#key_field_type {
                 #self_key_field_expr
             }
 
+            /// Query the entities that have `parent` as their closest ancestor
+            pub fn children_of<P>(parent: &P) -> Result<datastorers::DatastorersQuery<#name>, datastorers::DatastorersError>
+            where
+                P: datastorers::ParentKeyPathOf<#key_field_type> + datastorers::KeyPath,
+            {
+                use datastorers::DatastorersQueryable;
+
+                #name::query().ancestor(parent)
+            }
+
             pub async fn get_one_by_id(connection: &impl datastorers::DatastoreConnection, key_path: &#key_field_type) -> Result<#name, datastorers::DatastorersError>
             {
                 use datastorers::DatastorersQueryable;
@@ -286,10 +521,10 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
 
             fn try_from(mut entity: datastorers::DatastoreEntity) -> Result<Self, Self::Error> {
                 use std::convert::TryInto;
-                let key = entity
+                let entity_key = entity
                     .key()
-                    .ok_or(datastorers::DatastoreKeyError::NoKey)?
-                    .try_into()?;
+                    .ok_or(datastorers::DatastoreKeyError::NoKey)?;
+                let key = entity_key.clone().try_into()?;
                 let version = entity.version();
                 let mut properties = datastorers::DatastoreProperties::try_from(entity)?;
                 Ok(
@@ -300,6 +535,9 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
                         #(
                             #idents: #into_properties?,
                         )*
+                        #(
+                            #extra_field_assignements,
+                        )*
                     }
                 )
             }
@@ -310,7 +548,7 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
 
             fn try_from(entity: #name) -> Result<Self, Self::Error> {
                 use datastorers::KeyPath;
-                let mut properties = datastorers::DatastoreProperties::new();
+                let mut properties = #entity_properties;
                 #(
                     #from_properties?;
                 )*
@@ -329,6 +567,313 @@ pub fn datastore_managed(input: TokenStream) -> TokenStream {
     TokenStream::from(tokens)
 }
 
+#[proc_macro_derive(DatastoreEmbedded, attributes(property, indexed, unindexed))]
+pub fn datastore_embedded(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+
+    let mut idents: Vec<Ident> = Vec::new();
+    let mut set_methods: Vec<Ident> = Vec::new();
+    let descriptors: Vec<PropertyDescriptor> = match ast.data {
+        Data::Struct(vdata) => vdata
+            .fields
+            .iter()
+            .map(|field| {
+                let mut property_name: Option<String> = None;
+                let mut unindexed: bool = false;
+                for attr in &field.attrs {
+                    match attr.parse_meta().unwrap() {
+                        Meta::NameValue(name_value) if name_value.path.is_ident("property") => {
+                            match &name_value.lit {
+                                Lit::Str(lit_str) => {
+                                    property_name = Some(lit_str.value());
+                                }
+                                _ => panic!("invalid value type for property attribute"),
+                            }
+                        }
+                        Meta::Path(path) if path.is_ident("unindexed") => {
+                            unindexed = true;
+                        }
+                        Meta::List(list) if list.path.is_ident("indexed") => {
+                            unindexed = !indexed_value(&list);
+                        }
+                        _ => (),
+                    }
+                }
+                set_methods.push(match unindexed {
+                    true => format_ident!("set_unindexed_value"),
+                    false => format_ident!("set_value"),
+                });
+                let ident = field
+                    .ident
+                    .as_ref()
+                    .expect("You can only derive this on structs with named fields!");
+                idents.push(ident.clone());
+                let struct_property_name = ident.to_string();
+                let datastore_property_name =
+                    property_name.unwrap_or_else(|| struct_property_name.clone());
+                build_property_descriptor(
+                    &field.ty,
+                    &datastore_property_name,
+                    &struct_property_name,
+                )
+            })
+            .collect(),
+        Data::Enum(_) => panic!("You can only derive this on structs!"),
+        Data::Union(_) => panic!("You can only derive this on structs!"),
+    };
+
+    let name = &ast.ident;
+    let property_consts = descriptors.iter().map(|d| d.const_ident.clone());
+    let property_value_types = descriptors.iter().map(|d| d.value_type.clone());
+    let property_names = descriptors
+        .iter()
+        .map(|d| d.datastore_property.clone())
+        .collect::<Vec<_>>();
+
+    let tokens = quote! {
+        impl #name {
+            #(
+                pub const #property_consts: datastorers::Property<#name, #property_value_types> =
+                    datastorers::Property::new(#property_names);
+            )*
+        }
+
+        impl datastorers::serialize::Serialize for #name {
+            fn serialize(self) -> Result<Option<datastorers::DatastoreValue>, datastorers::serialize::DatastoreSerializeError> {
+                let mut properties = datastorers::DatastoreProperties::new();
+                #(
+                    properties.#set_methods(#property_names, self.#idents)?;
+                )*
+                datastorers::serialize::Serialize::serialize(properties)
+            }
+        }
+
+        impl datastorers::deserialize::Deserialize for #name {
+            fn deserialize(value: datastorers::DatastoreValue) -> Result<Self, datastorers::deserialize::DatastoreDeserializeError> {
+                let mut properties: datastorers::DatastoreProperties =
+                    datastorers::deserialize::Deserialize::deserialize(value)?;
+                Ok(
+                    #name {
+                        #(
+                            #idents: properties.get_value(#property_names)?,
+                        )*
+                    }
+                )
+            }
+        }
+    };
+
+    TokenStream::from(tokens)
+}
+
+#[proc_macro_derive(DatastoreEnum, attributes(property, stored_as, tag))]
+pub fn datastore_enum(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+
+    let mut integer_values = false;
+    let mut tag_property = String::from("type");
+    for attr in &ast.attrs {
+        match attr.parse_meta().unwrap() {
+            Meta::NameValue(name_value) if name_value.path.is_ident("stored_as") => {
+                match &name_value.lit {
+                    Lit::Str(lit_str) => match lit_str.value().as_str() {
+                        "string" => integer_values = false,
+                        "integer" => integer_values = true,
+                        _ => panic!("stored_as must be \"string\" or \"integer\""),
+                    },
+                    _ => panic!("invalid value type for stored_as attribute"),
+                }
+            }
+            Meta::NameValue(name_value) if name_value.path.is_ident("tag") => {
+                match &name_value.lit {
+                    Lit::Str(lit_str) => {
+                        tag_property = lit_str.value();
+                    }
+                    _ => panic!("invalid value type for tag attribute"),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let variants = match ast.data {
+        Data::Enum(data) => data.variants,
+        _ => panic!("You can only derive this on enums!"),
+    };
+
+    let name = &ast.ident;
+    let enum_name = name.to_string();
+    let mut next_discriminant: i64 = 0;
+    let mut serialize_arms = Vec::new();
+    let mut unit_arms = Vec::new();
+    let mut data_arms = Vec::new();
+    for variant in variants.iter() {
+        let ident = &variant.ident;
+        let property_name = property_attribute(&variant.attrs);
+        if let Some((_, discriminant)) = &variant.discriminant {
+            next_discriminant = parse_discriminant(discriminant);
+        }
+        // The value stored for a unit variant, or the tag of a data variant
+        let (tag_value, tag_pattern) = match integer_values {
+            true => {
+                if property_name.is_some() {
+                    panic!("property renames are not supported for enums stored as integers");
+                }
+                let value = parse_expr(&format!("{}i64", next_discriminant));
+                (value.clone(), value)
+            }
+            false => {
+                let tag = property_name.unwrap_or_else(|| ident.to_string());
+                (
+                    parse_expr(&format!("String::from({:?})", tag)),
+                    parse_expr(&format!("{:?}", tag)),
+                )
+            }
+        };
+        next_discriminant += 1;
+
+        match &variant.fields {
+            Fields::Unit => {
+                serialize_arms.push(quote! {
+                    #name::#ident => datastorers::serialize::Serialize::serialize(#tag_value)
+                });
+                unit_arms.push(quote! {
+                    #tag_pattern => Ok(#name::#ident)
+                });
+            }
+            fields => {
+                let bindings = (0..fields.len())
+                    .map(|index| format_ident!("field_{}", index))
+                    .collect::<Vec<_>>();
+                let property_names = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        property_attribute(&field.attrs).unwrap_or_else(|| match &field.ident {
+                            Some(field_ident) => field_ident.to_string(),
+                            None => index.to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let (pattern, construction) = match fields {
+                    Fields::Named(_) => {
+                        let field_idents = fields
+                            .iter()
+                            .map(|field| field.ident.clone().unwrap())
+                            .collect::<Vec<_>>();
+                        (
+                            quote! { #name::#ident { #(#field_idents: #bindings),* } },
+                            quote! {
+                                #name::#ident { #(#field_idents: properties.get_value(#property_names)?),* }
+                            },
+                        )
+                    }
+                    _ => (
+                        quote! { #name::#ident(#(#bindings),*) },
+                        quote! { #name::#ident(#(properties.get_value(#property_names)?),*) },
+                    ),
+                };
+                serialize_arms.push(quote! {
+                    #pattern => {
+                        let mut properties = datastorers::DatastoreProperties::new();
+                        properties.set_value(#tag_property, #tag_value)?;
+                        #(
+                            properties.set_value(#property_names, #bindings)?;
+                        )*
+                        datastorers::serialize::Serialize::serialize(properties)
+                    }
+                });
+                data_arms.push(quote! {
+                    #tag_pattern => Ok(#construction)
+                });
+            }
+        }
+    }
+
+    let (tag_type, tag_match) = match integer_values {
+        true => (quote! { i64 }, quote! { tag }),
+        false => (quote! { String }, quote! { tag.as_str() }),
+    };
+
+    let tokens = quote! {
+        impl datastorers::serialize::Serialize for #name {
+            fn serialize(self) -> Result<Option<datastorers::DatastoreValue>, datastorers::serialize::DatastoreSerializeError> {
+                match self {
+                    #(#serialize_arms,)*
+                }
+            }
+        }
+
+        impl datastorers::deserialize::Deserialize for #name {
+            fn deserialize(value: datastorers::DatastoreValue) -> Result<Self, datastorers::deserialize::DatastoreDeserializeError> {
+                if value.0.entity_value.is_some() {
+                    let mut properties: datastorers::DatastoreProperties =
+                        datastorers::deserialize::Deserialize::deserialize(value)?;
+                    let tag: #tag_type = properties.get_value(#tag_property)?;
+                    match #tag_match {
+                        #(#data_arms,)*
+                        _ => Err(datastorers::deserialize::DatastoreDeserializeError::UnknownVariant {
+                            enum_name: #enum_name,
+                            value: tag.to_string(),
+                        }),
+                    }
+                } else {
+                    let tag: #tag_type = datastorers::deserialize::Deserialize::deserialize(value)?;
+                    match #tag_match {
+                        #(#unit_arms,)*
+                        _ => Err(datastorers::deserialize::DatastoreDeserializeError::UnknownVariant {
+                            enum_name: #enum_name,
+                            value: tag.to_string(),
+                        }),
+                    }
+                }
+            }
+        }
+    };
+
+    TokenStream::from(tokens)
+}
+
+/// The value of `#[indexed(true)]` or `#[indexed(false)]`
+fn indexed_value(list: &MetaList) -> bool {
+    match list.nested.first() {
+        Some(NestedMeta::Lit(Lit::Bool(lit_bool))) if list.nested.len() == 1 => lit_bool.value,
+        _ => panic!("invalid indexed attribute, expected indexed(true) or indexed(false)"),
+    }
+}
+
+/// The renamed property of a field or variant, from `#[property = "Name"]`
+fn property_attribute(attrs: &[Attribute]) -> Option<String> {
+    let mut property_name = None;
+    for attr in attrs {
+        match attr.parse_meta().unwrap() {
+            Meta::NameValue(name_value) if name_value.path.is_ident("property") => {
+                match &name_value.lit {
+                    Lit::Str(lit_str) => {
+                        property_name = Some(lit_str.value());
+                    }
+                    _ => panic!("invalid value type for property attribute"),
+                }
+            }
+            _ => (),
+        }
+    }
+    property_name
+}
+
+fn parse_discriminant(discriminant: &Expr) -> i64 {
+    match discriminant {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Int(lit_int) => lit_int.base10_parse().unwrap(),
+            _ => panic!("enum discriminants must be integer literals"),
+        },
+        Expr::Unary(expr_unary) if matches!(expr_unary.op, UnOp::Neg(_)) => {
+            -parse_discriminant(&expr_unary.expr)
+        }
+        _ => panic!("enum discriminants must be integer literals"),
+    }
+}
+
 fn parse_expr(expr_string: &str) -> Expr {
     syn::parse_str::<Expr>(expr_string).expect("failed to parse expression")
 }
@@ -0,0 +1,147 @@
+use std::convert::TryFrom;
+
+use crate::connection::DatastoreConnection;
+use crate::deserialize::Deserialize;
+use crate::entity::{DatastoreEntity, DatastoreValue, Kind, Pagable};
+use crate::error::{DatastoreClientError, DatastorersError};
+use crate::property::FilterProperty;
+use crate::query::{DatastorersQuery, QueryBatches, KEY_PROPERTY};
+
+/// Numeric property values that can be aggregated with [sum](DatastorersQuery::sum)
+/// and [avg](DatastorersQuery::avg)
+pub trait Summable: Deserialize + Default + Copy {
+    /// Add two values, `None` if the sum does not fit in the type
+    fn checked_sum(self, other: Self) -> Option<Self>;
+    fn as_f64(self) -> f64;
+}
+
+macro_rules! summable_integer {
+    ($($int:ty),*) => {
+        $(
+            impl Summable for $int {
+                fn checked_sum(self, other: Self) -> Option<Self> {
+                    self.checked_add(other)
+                }
+
+                fn as_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+summable_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl Summable for f64 {
+    fn checked_sum(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Summable for f32 {
+    fn checked_sum(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl<E> DatastorersQuery<E>
+where
+    E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    /// Count the entities matching the query.
+    /// All pages are fetched with a keys-only query, the entities are never read.
+    ///
+    /// All matching entities are counted, queries with a limit fail with
+    /// [AggregateWithLimit](DatastoreClientError::AggregateWithLimit).
+    /// The same applies to [sum](DatastorersQuery::sum) and [avg](DatastorersQuery::avg).
+    pub async fn count(
+        self,
+        connection: &impl DatastoreConnection,
+    ) -> Result<usize, DatastorersError> {
+        let query = self.into_projection_query(String::from(KEY_PROPERTY))?;
+        let mut batches = QueryBatches::new(query);
+        let mut count = 0;
+        while let Some(batch) = batches.next_batch(connection).await? {
+            count += batch.len();
+        }
+        Ok(count)
+    }
+
+    /// Sum the values of a property of the entities matching the query.
+    ///
+    /// All pages are fetched with a projection query on the property, so the property must be
+    /// indexed. Entities without a value are not included, and for array properties every
+    /// value of the array is included.
+    ///
+    /// Integers are summed in the type of the property,
+    /// [SumOverflow](DatastoreClientError::SumOverflow) is returned if the sum does not fit.
+    pub async fn sum<P, T>(
+        self,
+        connection: &impl DatastoreConnection,
+        property: P,
+    ) -> Result<T, DatastorersError>
+    where
+        P: FilterProperty<E, T>,
+        T: Summable,
+    {
+        let (sum, _count) = self
+            .sum_and_count(connection, property.into_property_name())
+            .await?;
+        Ok(sum)
+    }
+
+    /// Average of the values of a property of the entities matching the query,
+    /// `None` if there are no values. The values are read like in [sum](DatastorersQuery::sum).
+    pub async fn avg<P, T>(
+        self,
+        connection: &impl DatastoreConnection,
+        property: P,
+    ) -> Result<Option<f64>, DatastorersError>
+    where
+        P: FilterProperty<E, T>,
+        T: Summable,
+    {
+        let (sum, count) = self
+            .sum_and_count::<T>(connection, property.into_property_name())
+            .await?;
+        match count {
+            0 => Ok(None),
+            _ => Ok(Some(sum.as_f64() / count as f64)),
+        }
+    }
+
+    async fn sum_and_count<T: Summable>(
+        self,
+        connection: &impl DatastoreConnection,
+        property: String,
+    ) -> Result<(T, usize), DatastorersError> {
+        let query = self.into_projection_query(property.clone())?;
+        let mut batches = QueryBatches::new(query);
+        let mut sum = T::default();
+        let mut count = 0;
+        while let Some(batch) = batches.next_batch(connection).await? {
+            for result in batch {
+                let value = result
+                    .entity
+                    .and_then(|entity| entity.properties)
+                    .and_then(|mut properties| properties.remove(&property));
+                if let Some(value) = value {
+                    sum = sum
+                        .checked_sum(T::deserialize(DatastoreValue(value))?)
+                        .ok_or_else(|| DatastoreClientError::SumOverflow(property.clone()))?;
+                    count += 1;
+                }
+            }
+        }
+        Ok((sum, count))
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use google_datastore1::schemas::{
+    Key, LookupResponse, Mutation, RunQueryRequest, RunQueryResponse,
+};
+use google_datastore1::Client;
+
+use crate::connection::DatastoreConnection;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// A connection that caches the results of lookups and queries.
+///
+/// Cached results are served until they are older than the TTL, or until an entity
+/// that they may contain is committed or deleted through this connection.
+/// Queries are invalidated by the kind of the written entity, lookups by its key.
+/// Queries without a known kind, such as GQL queries, are invalidated by every write.
+///
+/// Writes made through other connections, or by other processes, are not seen by the cache
+/// until the TTL expires.
+/// Results are never served from the cache inside a transaction, writes committed by a
+/// [TransactionConnection](crate::transaction::TransactionConnection) created from this
+/// connection invalidate the cache when the transaction is committed.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # use datastorers::cache::CachedConnection;
+/// # use std::time::Duration;
+/// fn with_cache<C: DatastoreConnection>(connection: C) -> CachedConnection<C> {
+///     CachedConnection::new(connection)
+///         .ttl(Duration::from_secs(10))
+///         .max_entries(500)
+/// }
+/// ```
+pub struct CachedConnection<C> {
+    connection: C,
+    cache: DatastoreCache,
+}
+
+impl<C: DatastoreConnection> CachedConnection<C> {
+    /// Wrap a connection, caching results for 60 seconds and at most 1000 results
+    pub fn new(connection: C) -> CachedConnection<C> {
+        CachedConnection {
+            connection,
+            cache: DatastoreCache::new(DEFAULT_TTL, DEFAULT_MAX_ENTRIES),
+        }
+    }
+
+    /// Set for how long results are cached
+    pub fn ttl(mut self, ttl: Duration) -> CachedConnection<C> {
+        self.cache.ttl = ttl;
+        self
+    }
+
+    /// Set the maximum number of cached results, the oldest results are evicted first
+    pub fn max_entries(mut self, max_entries: usize) -> CachedConnection<C> {
+        self.cache.max_entries = max_entries;
+        self
+    }
+
+    pub fn cache(&self) -> &DatastoreCache {
+        &self.cache
+    }
+
+    pub fn into_inner(self) -> C {
+        self.connection
+    }
+}
+
+impl<C: DatastoreConnection> DatastoreConnection for CachedConnection<C> {
+    fn get_client(&self) -> &Client {
+        self.connection.get_client()
+    }
+
+    fn get_project_name(&self) -> String {
+        self.connection.get_project_name()
+    }
+
+    fn get_transaction_id(&self) -> Option<String> {
+        self.connection.get_transaction_id()
+    }
+
+    fn get_cache(&self) -> Option<&DatastoreCache> {
+        Some(&self.cache)
+    }
+}
+
+#[derive(Clone)]
+enum CachedResponse {
+    Lookup {
+        keys: Vec<Key>,
+        response: LookupResponse,
+    },
+    // Kind is None if the kind of the query is not known
+    Query {
+        kind: Option<String>,
+        response: Box<RunQueryResponse>,
+    },
+}
+
+struct CacheEntry {
+    inserted: Instant,
+    response: CachedResponse,
+}
+
+impl CacheEntry {
+    fn is_affected_by(&self, key: &Key) -> bool {
+        match &self.response {
+            CachedResponse::Lookup { keys, .. } => keys.contains(key),
+            CachedResponse::Query { kind, .. } => match kind {
+                Some(kind) => Some(kind.as_str()) == key_kind(key),
+                None => true,
+            },
+        }
+    }
+}
+
+/// Cached lookup and query results of a [CachedConnection]
+pub struct DatastoreCache {
+    ttl: Duration,
+    max_entries: usize,
+    // Entries are stored by the Debug representation of the request. The cache lives in memory
+    // only, so the key only needs to be stable within one build of google_datastore1, whose
+    // request types print all fields in declaration order and maps in key order.
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl DatastoreCache {
+    fn new(ttl: Duration, max_entries: usize) -> DatastoreCache {
+        DatastoreCache {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remove all lookups of the key, and all queries of its kind
+    pub fn invalidate_key(&self, key: &Key) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, entry| !entry.is_affected_by(key));
+        }
+    }
+
+    /// Remove the results that may contain entities written by the mutations,
+    /// for writes committed without this connection
+    pub fn invalidate_mutations(&self, mutations: &[Mutation]) {
+        mutation_keys(mutations)
+            .iter()
+            .for_each(|key| self.invalidate_key(key));
+    }
+
+    /// Remove all lookups and queries of a kind
+    pub fn invalidate_kind(&self, kind: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, entry| match &entry.response {
+                CachedResponse::Lookup { keys, .. } => {
+                    keys.iter().all(|key| key_kind(key) != Some(kind))
+                }
+                CachedResponse::Query {
+                    kind: query_kind, ..
+                } => query_kind.is_some() && query_kind.as_deref() != Some(kind),
+            });
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.entries.lock() {
+            Ok(entries) => entries.len(),
+            Err(_) => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached response of a lookup of exactly these keys, in this order
+    pub fn lookup(&self, keys: &[Key]) -> Option<LookupResponse> {
+        match self.get(&format!("{:?}", keys))? {
+            CachedResponse::Lookup { response, .. } => Some(response),
+            CachedResponse::Query { .. } => None,
+        }
+    }
+
+    /// Cache the response of a lookup, unless some keys were deferred
+    pub fn insert_lookup(&self, keys: Vec<Key>, response: &LookupResponse) {
+        // Deferred keys were not looked up, the response is not complete
+        if response.deferred.iter().flatten().next().is_some() {
+            return;
+        }
+        self.insert(
+            format!("{:?}", keys),
+            CachedResponse::Lookup {
+                keys,
+                response: response.clone(),
+            },
+        );
+    }
+
+    /// The cached response of a query request
+    pub fn query(&self, req: &RunQueryRequest) -> Option<RunQueryResponse> {
+        match self.get(&format!("{:?}", req))? {
+            CachedResponse::Query { response, .. } => Some(*response),
+            CachedResponse::Lookup { .. } => None,
+        }
+    }
+
+    /// Cache the response of a query request, by the kind of the query if it has one
+    pub fn insert_query(&self, req: &RunQueryRequest, response: &RunQueryResponse) {
+        let kind = req
+            .query
+            .as_ref()
+            .and_then(|query| query.kind.as_ref())
+            .and_then(|kinds| kinds.first())
+            .and_then(|kind| kind.name.clone());
+        self.insert(
+            format!("{:?}", req),
+            CachedResponse::Query {
+                kind,
+                response: Box::new(response.clone()),
+            },
+        );
+    }
+
+    fn get(&self, request: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().ok()?;
+        let entry = entries.get(request)?;
+        if entry.inserted.elapsed() > self.ttl {
+            entries.remove(request);
+            return None;
+        }
+        Some(entry.response.clone())
+    }
+
+    fn insert(&self, request: String, response: CachedResponse) {
+        if self.max_entries == 0 {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= self.max_entries {
+                let ttl = self.ttl;
+                entries.retain(|_, entry| entry.inserted.elapsed() <= ttl);
+            }
+            while entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted)
+                    .map(|(request, _)| request.clone());
+                match oldest {
+                    Some(oldest) => entries.remove(&oldest),
+                    None => break,
+                };
+            }
+            entries.insert(
+                request,
+                CacheEntry {
+                    inserted: Instant::now(),
+                    response,
+                },
+            );
+        }
+    }
+}
+
+/// The cache of a connection, if results may be read from it.
+/// Results are never read from the cache inside a transaction.
+pub(crate) fn readable_cache(connection: &impl DatastoreConnection) -> Option<&DatastoreCache> {
+    match connection.get_transaction_id() {
+        Some(_) => None,
+        None => connection.get_cache(),
+    }
+}
+
+fn key_kind(key: &Key) -> Option<&str> {
+    key.path
+        .as_ref()
+        .and_then(|path| path.last())
+        .and_then(|element| element.kind.as_deref())
+}
+
+/// Keys of the entities written by mutations, for invalidation after a commit
+pub(crate) fn mutation_keys(mutations: &[Mutation]) -> Vec<Key> {
+    mutations
+        .iter()
+        .filter_map(|mutation| {
+            mutation.delete.clone().or_else(|| {
+                mutation
+                    .upsert
+                    .as_ref()
+                    .or(mutation.insert.as_ref())
+                    .or(mutation.update.as_ref())
+                    .and_then(|entity| entity.key.clone())
+            })
+        })
+        .collect()
+}
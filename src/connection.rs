@@ -1,3 +1,4 @@
+use crate::cache::DatastoreCache;
 use google_datastore1::Client;
 
 pub trait DatastoreConnection
@@ -11,4 +12,9 @@ where
     fn get_transaction_id(&self) -> Option<String> {
         None
     }
+
+    // Cache of lookup and query results, see CachedConnection
+    fn get_cache(&self) -> Option<&DatastoreCache> {
+        None
+    }
 }
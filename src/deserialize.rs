@@ -1,18 +1,41 @@
 use crate::bytes::Bytes;
-use crate::DatastoreValue;
-use chrono::{DateTime, NaiveDateTime, ParseError};
+use crate::error::{DatastoreGeoError, DatastoreKeyError, DatastoreNameRepresentationError};
+use crate::{DatastoreProperties, DatastoreValue};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, ParseError, Utc};
 use radix64::{DecodeError, STD as BASE64_CFG};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::hash::BuildHasher;
+use std::time::SystemTime;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum DatastoreDeserializeError {
     #[error("value not found")]
     NoSuchValue,
+    #[error("unexpected {found} value")]
+    WrongValueType { found: &'static str },
     #[error(transparent)]
     Base64DecodeError(#[from] DecodeError),
     #[error(transparent)]
     ParseError(#[from] ParseError),
+    #[error("integer {value} is out of range for {target}")]
+    IntegerOutOfRange { value: i64, target: &'static str },
+    #[error("unknown variant {value} of enum {enum_name}")]
+    UnknownVariant {
+        enum_name: &'static str,
+        value: String,
+    },
+    #[error(transparent)]
+    GeoError(#[from] DatastoreGeoError),
+    #[error(transparent)]
+    KeyError(#[from] DatastoreKeyError),
+    #[error(transparent)]
+    NameRepresentationError(#[from] DatastoreNameRepresentationError),
+    #[cfg(feature = "serde")]
+    #[error("could not deserialize serde value: {0}")]
+    SerdeError(String),
 }
 
 pub trait Deserialize
@@ -67,6 +90,26 @@ impl<T: Deserialize> Deserialize for Vec<T> {
     }
 }
 
+/// Blanket impl for BTreeMap, read from an embedded entity with the property names as keys
+impl<T: Deserialize> Deserialize for BTreeMap<String, T> {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        DatastoreProperties::deserialize(value)?
+            .into_map()
+            .into_iter()
+            .map(|(key, value)| Ok((key, T::deserialize(DatastoreValue(value))?)))
+            .collect()
+    }
+}
+
+/// Blanket impl for HashMap, read like BTreeMap
+impl<T: Deserialize, S: BuildHasher + Default> Deserialize for HashMap<String, T, S> {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        Ok(BTreeMap::<String, T>::deserialize(value)?
+            .into_iter()
+            .collect())
+    }
+}
+
 impl Deserialize for String {
     fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
         value
@@ -85,6 +128,25 @@ impl Deserialize for i64 {
     }
 }
 
+/// Integer types that an i64 is converted to, with a range check
+macro_rules! deserialize_integer {
+    ($($int:ty),*) => {
+        $(
+            impl Deserialize for $int {
+                fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+                    let value = i64::deserialize(value)?;
+                    <$int>::try_from(value).map_err(|_| DatastoreDeserializeError::IntegerOutOfRange {
+                        value,
+                        target: stringify!($int),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+deserialize_integer!(i8, i16, i32, isize, u8, u16, u32, u64, usize);
+
 impl Deserialize for f64 {
     fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
         value
@@ -94,6 +156,12 @@ impl Deserialize for f64 {
     }
 }
 
+impl Deserialize for f32 {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        f64::deserialize(value).map(|value| value as f32)
+    }
+}
+
 impl Deserialize for bool {
     fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
         value
@@ -114,13 +182,43 @@ impl Deserialize for Bytes {
     }
 }
 
-impl Deserialize for NaiveDateTime {
+impl Deserialize for DateTime<FixedOffset> {
     fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
         let date_string = value
             .0
             .timestamp_value
             .ok_or(DatastoreDeserializeError::NoSuchValue)?;
         let date_time = DateTime::parse_from_rfc3339(&date_string)?;
+        // Normalised to UTC, as it is stored
+        Ok(date_time.with_timezone(&Utc).into())
+    }
+}
+
+impl Deserialize for DateTime<Utc> {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let date_time = DateTime::<FixedOffset>::deserialize(value)?;
+        Ok(date_time.with_timezone(&Utc))
+    }
+}
+
+impl Deserialize for NaiveDateTime {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let date_time = DateTime::<FixedOffset>::deserialize(value)?;
         Ok(date_time.naive_utc())
     }
 }
+
+/// The date of the timestamp in UTC
+impl Deserialize for NaiveDate {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let date_time = NaiveDateTime::deserialize(value)?;
+        Ok(date_time.date())
+    }
+}
+
+impl Deserialize for SystemTime {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let date_time = DateTime::<Utc>::deserialize(value)?;
+        Ok(date_time.into())
+    }
+}
@@ -1,9 +1,12 @@
-use crate::error::DatastoreParseError;
+use crate::error::{DatastoreParseError, DatastorePropertyError};
 
 use google_datastore1::schemas::{Entity, EntityResult, Key, Query, Value};
+use radix64::STD as BASE64_CFG;
 
-use crate::deserialize::Deserialize;
-use crate::serialize::Serialize;
+use crate::deserialize::{DatastoreDeserializeError, Deserialize};
+use crate::identifier::KeyPath;
+use crate::query_display::KeyDisplay;
+use crate::serialize::{DatastoreSerializeError, Serialize};
 use crate::DatastorersError;
 use std::collections::BTreeMap;
 use std::convert::From;
@@ -22,6 +25,12 @@ pub trait Kind {
     fn kind_str() -> &'static str;
 }
 
+/// The key path type of an entity, implemented by the `DatastoreManaged` derive
+/// with the type of the `#[key]` field.
+pub trait Keyed {
+    type Key: KeyPath;
+}
+
 pub trait Pagable {
     // Get default page size for Entity, if one is present
     fn page_size() -> Option<i32>;
@@ -30,6 +39,7 @@ pub trait Pagable {
 //
 // DatastoreValue
 //
+#[derive(Debug, Clone)]
 pub struct DatastoreValue(pub Value);
 
 impl DatastoreValue {
@@ -75,7 +85,7 @@ impl From<DatastoreValue> for Value {
 //
 // DatastoreProperties
 //
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct DatastoreProperties(BTreeMap<String, Value>);
 
 impl Display for DatastoreProperties {
@@ -85,7 +95,6 @@ impl Display for DatastoreProperties {
 }
 
 impl DatastoreProperties {
-    #[allow(clippy::new_without_default)]
     pub fn new() -> DatastoreProperties {
         DatastoreProperties(BTreeMap::<String, Value>::new())
     }
@@ -109,13 +118,200 @@ impl DatastoreProperties {
     }
 
     pub fn set<T: Serialize>(&mut self, key: &str, value: T) -> Result<(), DatastorersError> {
-        if let Some(value) = value.serialize()? {
+        self.set_value(key, value).map_err(|e| e.into())
+    }
+
+    /// Get a property of the entity of `kind` with the key `key`, used by the
+    /// `DatastoreManaged` derive. Errors describe the entity, the property and the value found.
+    pub fn get_property<T: Deserialize>(
+        &mut self,
+        kind: &'static str,
+        key: &Key,
+        property: &str,
+    ) -> Result<T, DatastorersError> {
+        let value = self.0.remove(property);
+        let found = value.as_ref().map_or("missing", value_type);
+        let result = match value {
+            Some(value) => T::deserialize(DatastoreValue(value)),
+            None => T::default_missing().ok_or(DatastoreDeserializeError::NoSuchValue),
+        };
+        result.map_err(|source| {
+            let source = match source {
+                // A value was found, but not of a type that can be read as T
+                DatastoreDeserializeError::NoSuchValue if found != "missing" && found != "null" => {
+                    DatastoreDeserializeError::WrongValueType { found }
+                }
+                source => source,
+            };
+            DatastorePropertyError {
+                kind,
+                key: KeyDisplay(key).to_string(),
+                property: property.to_string(),
+                expected: short_type_name(std::any::type_name::<T>()),
+                found,
+                source,
+            }
+            .into()
+        })
+    }
+
+    /// Get a property value of an embedded entity,
+    /// a missing value is a [NoSuchValue](DatastoreDeserializeError::NoSuchValue) error
+    pub fn get_value<T: Deserialize>(&mut self, key: &str) -> Result<T, DatastoreDeserializeError> {
+        match self.0.remove(key) {
+            Some(value) => T::deserialize(DatastoreValue(value)),
+            None => T::default_missing().ok_or(DatastoreDeserializeError::NoSuchValue),
+        }
+    }
+
+    /// Set a property value of an embedded entity
+    pub fn set_value<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<(), DatastoreSerializeError> {
+        if let Some(mut value) = value.serialize()? {
+            exclude_long_values(&mut value.0);
+            self.0.insert(key.to_string(), value.0);
+        }
+        Ok(())
+    }
+
+    /// Set a property that is excluded from indexes, the property can not be used in queries
+    pub fn set_unindexed<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<(), DatastorersError> {
+        self.set_unindexed_value(key, value).map_err(|e| e.into())
+    }
+
+    /// Set a property value of an embedded entity, excluded from indexes
+    pub fn set_unindexed_value<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<(), DatastoreSerializeError> {
+        if let Some(mut value) = value.serialize()? {
+            exclude_from_indexes(&mut value.0);
             self.0.insert(key.to_string(), value.0);
         }
         Ok(())
     }
 }
 
+/// A type name without module paths, e.g. `Option<String>` for `core::option::Option<alloc::string::String>`
+fn short_type_name(type_name: &str) -> String {
+    let mut short = String::new();
+    let mut path = String::new();
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            short.push_str(path.rsplit("::").next().unwrap_or_default());
+            path.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(path.rsplit("::").next().unwrap_or_default());
+    short
+}
+
+fn value_type(value: &Value) -> &'static str {
+    if value.null_value.is_some() {
+        "null"
+    } else if value.boolean_value.is_some() {
+        "boolean"
+    } else if value.integer_value.is_some() {
+        "integer"
+    } else if value.double_value.is_some() {
+        "double"
+    } else if value.timestamp_value.is_some() {
+        "timestamp"
+    } else if value.key_value.is_some() {
+        "key"
+    } else if value.string_value.is_some() {
+        "string"
+    } else if value.blob_value.is_some() {
+        "blob"
+    } else if value.geo_point_value.is_some() {
+        "geo point"
+    } else if value.array_value.is_some() {
+        "array"
+    } else if value.entity_value.is_some() {
+        "entity"
+    } else {
+        "empty value"
+    }
+}
+
+// Datastore can not index strings and blobs longer than this,
+// longer values are excluded from indexes when they are stored.
+const MAX_INDEXED_BYTES: usize = 1500;
+
+fn exclude_long_values(value: &mut Value) {
+    if let Some(array) = value.array_value.as_mut() {
+        array
+            .values
+            .iter_mut()
+            .flatten()
+            .for_each(exclude_long_values);
+    } else if let Some(entity) = value.entity_value.as_mut() {
+        // The properties of embedded entities are indexed like other properties
+        entity
+            .properties
+            .iter_mut()
+            .flat_map(|properties| properties.values_mut())
+            .for_each(exclude_long_values);
+    } else {
+        let string_bytes = value.string_value.as_ref().map(String::len);
+        // Blobs are stored base64 encoded, the limit applies to the decoded bytes
+        let blob_bytes = value.blob_value.as_ref().map(|blob| {
+            BASE64_CFG
+                .decode(blob)
+                .map_or(blob.len(), |bytes| bytes.len())
+        });
+        if string_bytes.or(blob_bytes).unwrap_or_default() > MAX_INDEXED_BYTES {
+            value.exclude_from_indexes = Some(true);
+        }
+    }
+}
+
+fn exclude_from_indexes(value: &mut Value) {
+    match value.array_value.as_mut() {
+        // Arrays can not be excluded from indexes, each of their values is excluded instead
+        Some(array) => array
+            .values
+            .iter_mut()
+            .flatten()
+            .for_each(|value| value.exclude_from_indexes = Some(true)),
+        None => value.exclude_from_indexes = Some(true),
+    }
+}
+
+/// Stored as an embedded entity, without a key
+impl Serialize for DatastoreProperties {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        let mut value = DatastoreValue::empty();
+        value.entity_value = Some(Entity {
+            key: None,
+            properties: Some(self.0),
+        });
+        Ok(Some(value))
+    }
+}
+
+/// The properties of an embedded entity
+impl Deserialize for DatastoreProperties {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let entity = value
+            .0
+            .entity_value
+            .ok_or(DatastoreDeserializeError::NoSuchValue)?;
+        Ok(DatastoreProperties(entity.properties.unwrap_or_default()))
+    }
+}
+
 impl TryFrom<DatastoreEntity> for DatastoreProperties {
     type Error = DatastorersError;
 
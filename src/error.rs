@@ -39,6 +39,17 @@ pub enum DatastoreClientError {
     TransactionInProgress,
     #[error("missing filter props")]
     NoFilterProps,
+    #[error("parallel scans cannot be sorted by other properties than the key")]
+    ScanOrderNotSupported,
+    #[error("aggregations run over all results of a query, a limit is not supported")]
+    AggregateWithLimit,
+    #[error("sum of property {0:?} does not fit in the property type")]
+    SumOverflow(String),
+    #[error("gql query kind mismatch, expected {expected:?} found {found:?}")]
+    GqlKindMismatch {
+        expected: &'static str,
+        found: Option<String>,
+    },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -60,6 +71,8 @@ pub enum DatastoreKeyError {
     ExpectedId,
     #[error("expected identifier of type name (String) but did not find it")]
     ExpectedName,
+    #[error("key path has {found} elements, expected {expected}")]
+    WrongKeyPathLength { expected: usize, found: usize },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -68,6 +81,39 @@ pub enum DatastoreNameRepresentationError {
     ParseStrError,
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum DatastoreIndexError {
+    #[error("invalid index.yaml, line {line}: {reason}")]
+    InvalidIndexYaml { line: usize, reason: &'static str },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DatastoreGeoError {
+    #[error("latitude {0} is out of range, expected -90 to 90")]
+    LatitudeOutOfRange(f64),
+    #[error("longitude {0} is out of range, expected -180 to 180")]
+    LongitudeOutOfRange(f64),
+    #[error("invalid geohash {0:?}")]
+    InvalidGeohash(String),
+    #[error("south west corner of box is north or east of the north east corner")]
+    InvalidBox,
+}
+
+/// A property of an entity that could not be read
+#[derive(Error, Debug, PartialEq)]
+#[error("could not read property {property:?} of {kind} entity {key}, expected {expected} found {found}")]
+pub struct DatastorePropertyError {
+    pub kind: &'static str,
+    /// The key of the entity as a GQL key literal
+    pub key: String,
+    pub property: String,
+    /// The rust type the property was read as, without module paths
+    pub expected: String,
+    /// The type of the value that was found, `missing` if there was no value
+    pub found: &'static str,
+    pub source: DatastoreDeserializeError,
+}
+
 #[derive(Error, Debug)]
 pub enum DatastorersError {
     #[error(transparent)]
@@ -81,7 +127,19 @@ pub enum DatastorersError {
     #[error(transparent)]
     DatastoreDeserializeError(#[from] DatastoreDeserializeError),
     #[error(transparent)]
+    DatastorePropertyError(#[from] Box<DatastorePropertyError>),
+    #[error(transparent)]
     DatastoreKeyError(#[from] DatastoreKeyError),
     #[error(transparent)]
     DatastoreNameRepresentationError(#[from] DatastoreNameRepresentationError),
+    #[error(transparent)]
+    DatastoreIndexError(#[from] DatastoreIndexError),
+    #[error(transparent)]
+    DatastoreGeoError(#[from] DatastoreGeoError),
+}
+
+impl From<DatastorePropertyError> for DatastorersError {
+    fn from(error: DatastorePropertyError) -> DatastorersError {
+        Box::new(error).into()
+    }
 }
@@ -0,0 +1,274 @@
+use std::convert::TryFrom;
+
+use google_datastore1::schemas::LatLng;
+
+use crate::deserialize::{DatastoreDeserializeError, Deserialize};
+use crate::entity::{DatastoreEntity, DatastoreValue, Kind, Pagable};
+use crate::error::{DatastoreGeoError, DatastorersError};
+use crate::property::FilterProperty;
+use crate::query::DatastorersQuery;
+use crate::serialize::{DatastoreSerializeError, Serialize};
+
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+const MAX_GEOHASH_PRECISION: usize = 12;
+// The maximum number of geohash cells used to cover a box
+const MAX_COVERING_CELLS: usize = 9;
+const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+
+/// A point on earth, stored as a `geo_point_value`.
+///
+/// Datastore can not filter on distance or areas, to find points within an area
+/// store the [geohash](GeoPoint::geohash) of the point in a separate string property,
+/// and query it with [geohash_within](DatastorersQuery::geohash_within).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    pub fn new(latitude: f64, longitude: f64) -> Result<GeoPoint, DatastoreGeoError> {
+        let point = GeoPoint {
+            latitude,
+            longitude,
+        };
+        point.validate()?;
+        Ok(point)
+    }
+
+    /// Latitude must be within [-90, 90] and longitude within [-180, 180]
+    pub fn validate(&self) -> Result<(), DatastoreGeoError> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(DatastoreGeoError::LatitudeOutOfRange(self.latitude));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(DatastoreGeoError::LongitudeOutOfRange(self.longitude));
+        }
+        Ok(())
+    }
+
+    /// The geohash of the point with `precision` characters, at most 12.
+    /// Points that share a geohash prefix are within the same geohash cell.
+    pub fn geohash(&self, precision: usize) -> String {
+        let mut latitude_range = (-90.0, 90.0);
+        let mut longitude_range = (-180.0, 180.0);
+        let mut geohash = String::new();
+        let mut even_bit = true;
+        for _ in 0..precision.min(MAX_GEOHASH_PRECISION) {
+            let mut index = 0;
+            for _ in 0..5 {
+                let (range, value) = match even_bit {
+                    true => (&mut longitude_range, self.longitude),
+                    false => (&mut latitude_range, self.latitude),
+                };
+                let middle = (range.0 + range.1) / 2.0;
+                index <<= 1;
+                if value >= middle {
+                    index |= 1;
+                    range.0 = middle;
+                } else {
+                    range.1 = middle;
+                }
+                even_bit = !even_bit;
+            }
+            geohash.push(GEOHASH_ALPHABET[index] as char);
+        }
+        geohash
+    }
+
+    /// The great-circle distance to another point, in meters
+    pub fn distance_meters(&self, other: &GeoPoint) -> f64 {
+        let latitude_delta = (other.latitude - self.latitude).to_radians();
+        let longitude_delta = (other.longitude - self.longitude).to_radians();
+        let a = (latitude_delta / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos()
+                * other.latitude.to_radians().cos()
+                * (longitude_delta / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()
+    }
+}
+
+impl Serialize for GeoPoint {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        self.validate()?;
+        let mut value = DatastoreValue::empty();
+        value.geo_point_value = Some(LatLng {
+            latitude: Some(self.latitude),
+            longitude: Some(self.longitude),
+        });
+        Ok(Some(value))
+    }
+}
+
+impl Deserialize for GeoPoint {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let lat_lng = value
+            .0
+            .geo_point_value
+            .ok_or(DatastoreDeserializeError::NoSuchValue)?;
+        match (lat_lng.latitude, lat_lng.longitude) {
+            (Some(latitude), Some(longitude)) => Ok(GeoPoint::new(latitude, longitude)?),
+            _ => Err(DatastoreDeserializeError::NoSuchValue),
+        }
+    }
+}
+
+/// An area between two latitudes and two longitudes.
+/// Boxes that cross the antimeridian are not supported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoBox {
+    pub south_west: GeoPoint,
+    pub north_east: GeoPoint,
+}
+
+impl GeoBox {
+    pub fn new(south_west: GeoPoint, north_east: GeoPoint) -> Result<GeoBox, DatastoreGeoError> {
+        south_west.validate()?;
+        north_east.validate()?;
+        if south_west.latitude > north_east.latitude || south_west.longitude > north_east.longitude
+        {
+            return Err(DatastoreGeoError::InvalidBox);
+        }
+        Ok(GeoBox {
+            south_west,
+            north_east,
+        })
+    }
+
+    /// The box around all points within `radius_meters` of `center`,
+    /// use [distance_meters](GeoPoint::distance_meters) to filter the points in the corners.
+    pub fn around(center: GeoPoint, radius_meters: f64) -> GeoBox {
+        let latitude_delta = (radius_meters / EARTH_RADIUS_METERS).to_degrees();
+        let north = (center.latitude + latitude_delta).min(90.0);
+        let south = (center.latitude - latitude_delta).max(-90.0);
+        // Longitude degrees are shortest at the latitude furthest from the equator
+        let widest_latitude = north.abs().max(south.abs()).to_radians();
+        let longitude_delta = match widest_latitude.cos() {
+            cos if cos > f64::EPSILON => latitude_delta / cos,
+            _ => 180.0,
+        };
+        GeoBox {
+            south_west: GeoPoint {
+                latitude: south,
+                longitude: (center.longitude - longitude_delta).max(-180.0),
+            },
+            north_east: GeoPoint {
+                latitude: north,
+                longitude: (center.longitude + longitude_delta).min(180.0),
+            },
+        }
+    }
+
+    /// The cell of a geohash
+    pub fn from_geohash(geohash: &str) -> Result<GeoBox, DatastoreGeoError> {
+        let mut latitude_range = (-90.0, 90.0);
+        let mut longitude_range = (-180.0, 180.0);
+        let mut even_bit = true;
+        for c in geohash.chars() {
+            let index = GEOHASH_ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .ok_or_else(|| DatastoreGeoError::InvalidGeohash(geohash.to_string()))?;
+            for bit in (0..5).rev() {
+                let range = match even_bit {
+                    true => &mut longitude_range,
+                    false => &mut latitude_range,
+                };
+                let middle = (range.0 + range.1) / 2.0;
+                if index & (1 << bit) != 0 {
+                    range.0 = middle;
+                } else {
+                    range.1 = middle;
+                }
+                even_bit = !even_bit;
+            }
+        }
+        Ok(GeoBox {
+            south_west: GeoPoint {
+                latitude: latitude_range.0,
+                longitude: longitude_range.0,
+            },
+            north_east: GeoPoint {
+                latitude: latitude_range.1,
+                longitude: longitude_range.1,
+            },
+        })
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        (self.south_west.latitude..=self.north_east.latitude).contains(&point.latitude)
+            && (self.south_west.longitude..=self.north_east.longitude).contains(&point.longitude)
+    }
+
+    /// The geohash prefixes of the cells covering the box, using the longest prefixes that
+    /// cover the box with at most 9 cells. The cells may cover points outside of the box.
+    ///
+    /// Boxes that need more than 9 of the 32 cells of precision 1, such as boxes spanning
+    /// continents, are covered by the empty prefix, which matches every geohash.
+    pub fn geohash_prefixes(&self) -> Vec<String> {
+        for precision in (1..=MAX_GEOHASH_PRECISION).rev() {
+            let prefixes = self.covering_cells(precision);
+            if !prefixes.is_empty() {
+                return prefixes;
+            }
+        }
+        vec![String::new()]
+    }
+
+    // The cells of a precision covering the box, empty if more than MAX_COVERING_CELLS are needed
+    fn covering_cells(&self, precision: usize) -> Vec<String> {
+        let latitude_bits = 5 * precision / 2;
+        let longitude_bits = 5 * precision - latitude_bits;
+        let cell_height = 180.0 / f64::from(1u32 << latitude_bits);
+        let cell_width = 360.0 / f64::from(1u32 << longitude_bits);
+        let row = |latitude: f64| ((latitude + 90.0) / cell_height).floor() as i64;
+        let column = |longitude: f64| ((longitude + 180.0) / cell_width).floor() as i64;
+
+        let rows = row(self.south_west.latitude)..=row(self.north_east.latitude);
+        let columns = column(self.south_west.longitude)..=column(self.north_east.longitude);
+        let cells = (rows.end() - rows.start() + 1) * (columns.end() - columns.start() + 1);
+        if cells > MAX_COVERING_CELLS as i64 {
+            return vec![];
+        }
+        let mut prefixes = vec![];
+        for row in rows {
+            for column in columns.clone() {
+                // Geohash of the cell center, clamped for the edges at 90 and 180 degrees
+                let center = GeoPoint {
+                    latitude: ((row as f64 + 0.5) * cell_height - 90.0).min(90.0),
+                    longitude: ((column as f64 + 0.5) * cell_width - 180.0).min(180.0),
+                };
+                let prefix = center.geohash(precision);
+                if !prefixes.contains(&prefix) {
+                    prefixes.push(prefix);
+                }
+            }
+        }
+        prefixes
+    }
+}
+
+impl<E> DatastorersQuery<E>
+where
+    E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    /// Queries for the entities with a geohash property within the cells covering `area`,
+    /// one query per [covering geohash prefix](GeoBox::geohash_prefixes).
+    ///
+    /// The property must hold geohashes of at least the precision of the prefixes,
+    /// and the results may include points outside of the area, filter them with
+    /// [contains](GeoBox::contains).
+    pub fn geohash_within<P>(
+        self,
+        property: P,
+        area: &GeoBox,
+    ) -> Result<Vec<DatastorersQuery<E>>, DatastorersError>
+    where
+        P: FilterProperty<E, String> + Clone,
+    {
+        area.geohash_prefixes()
+            .iter()
+            .map(|prefix| self.clone().starts_with(property.clone(), prefix))
+            .collect()
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+use crate::connection::DatastoreConnection;
+use crate::entity::{DatastoreEntity, Kind, ResultCollection};
+use crate::error::{DatastoreClientError, DatastorersError};
+use crate::query::{page_from_batch, run_query};
+use crate::serialize::{DatastoreSerializeError, Serialize};
+
+use google_datastore1::schemas::{GqlQuery, GqlQueryParameter, ReadOptions, RunQueryRequest};
+
+/// Create a [GQL](https://cloud.google.com/datastore/docs/reference/gql_reference) query
+/// returning entities of type `E`.
+///
+/// Values are bound with [bind](DatastorersGqlQuery::bind) for named bindings (`@name`)
+/// and [bind_positional](DatastorersGqlQuery::bind_positional) for positional bindings
+/// (`@1`, `@2`, ...). The kind in the `FROM` clause must be the kind of `E`,
+/// otherwise [GqlKindMismatch](DatastoreClientError::GqlKindMismatch) is returned.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="Test"]
+/// # struct TestEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// # }
+/// async fn fetch_by_name(
+///     connection: &impl DatastoreConnection,
+/// ) -> Result<ResultCollection<TestEntity>, DatastorersError> {
+///     gql::<TestEntity>("SELECT * FROM Test WHERE Name = @name")?
+///         .bind("name", "test-name".to_string())?
+///         .fetch(connection)
+///         .await
+/// }
+/// ```
+pub fn gql<E>(query_string: &str) -> Result<DatastorersGqlQuery<E>, DatastorersError>
+where
+    E: Kind + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    let kind = gql_kind(query_string);
+    if kind.as_deref() != Some(E::kind_str()) {
+        return Err(DatastoreClientError::GqlKindMismatch {
+            expected: E::kind_str(),
+            found: kind,
+        }
+        .into());
+    }
+
+    Ok(DatastorersGqlQuery {
+        entity: PhantomData,
+        query_string: query_string.to_string(),
+        named_bindings: BTreeMap::new(),
+        positional_bindings: Vec::new(),
+        allow_literals: false,
+    })
+}
+
+pub struct DatastorersGqlQuery<E>
+where
+    E: Kind + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    entity: PhantomData<E>,
+    query_string: String,
+    named_bindings: BTreeMap<String, GqlQueryParameter>,
+    positional_bindings: Vec<GqlQueryParameter>,
+    allow_literals: bool,
+}
+
+impl<E> DatastorersGqlQuery<E>
+where
+    E: Kind + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    /// Bind a value to a named binding, `@name` in the query string is bound with `bind("name", value)`
+    pub fn bind(
+        mut self,
+        name: &str,
+        value: impl Serialize,
+    ) -> Result<DatastorersGqlQuery<E>, DatastorersError> {
+        let parameter = gql_parameter(value)?;
+        self.named_bindings.insert(name.to_string(), parameter);
+        Ok(self)
+    }
+
+    /// Bind a value to the next positional binding, the first call binds `@1`, the second `@2` and so on
+    pub fn bind_positional(
+        mut self,
+        value: impl Serialize,
+    ) -> Result<DatastorersGqlQuery<E>, DatastorersError> {
+        let parameter = gql_parameter(value)?;
+        self.positional_bindings.push(parameter);
+        Ok(self)
+    }
+
+    /// Allow literal values in the query string, by default only bindings are allowed
+    pub fn allow_literals(mut self) -> DatastorersGqlQuery<E> {
+        self.allow_literals = true;
+        self
+    }
+
+    pub async fn fetch(
+        self,
+        connection: &impl DatastoreConnection,
+    ) -> Result<ResultCollection<E>, DatastorersError> {
+        let req = RunQueryRequest {
+            gql_query: Some(GqlQuery {
+                query_string: Some(self.query_string),
+                named_bindings: match self.named_bindings.len() {
+                    0 => None,
+                    _ => Some(self.named_bindings),
+                },
+                positional_bindings: match self.positional_bindings.len() {
+                    0 => None,
+                    _ => Some(self.positional_bindings),
+                },
+                allow_literals: Some(self.allow_literals),
+            }),
+            read_options: Some(ReadOptions {
+                transaction: connection.get_transaction_id(),
+                read_consistency: None,
+            }),
+            ..Default::default()
+        };
+        let resp = run_query(connection, req).await?;
+
+        // The response contains the parsed form of the gql query, it is used to fetch more pages
+        let query = resp.query.ok_or(DatastoreClientError::ApiDataError)?;
+        let page = page_from_batch(resp.batch, query)?;
+        let result = page.try_into()?;
+
+        Ok(result)
+    }
+}
+
+fn gql_parameter(value: impl Serialize) -> Result<GqlQueryParameter, DatastorersError> {
+    let ds_value = value
+        .serialize()?
+        .ok_or(DatastoreSerializeError::NoValueError)?;
+    Ok(GqlQueryParameter {
+        value: Some(ds_value.into()),
+        cursor: None,
+    })
+}
+
+/// Find the kind in the `FROM` clause of a gql query string.
+/// String literals are skipped and back quoted kinds are unquoted.
+fn gql_kind(query_string: &str) -> Option<String> {
+    let mut chars = query_string.chars().peekable();
+    let mut previous_was_from = false;
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\'' | '"' => {
+                // Skip string literal, quotes are escaped by doubling them or with a backslash
+                while let Some(s) = chars.next() {
+                    if s == '\\' {
+                        chars.next();
+                    } else if s == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                previous_was_from = false;
+                continue;
+            }
+            '`' => {
+                let mut name = String::new();
+                while let Some(s) = chars.next() {
+                    if s == '`' {
+                        if chars.peek() == Some(&'`') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    name.push(s);
+                }
+                if previous_was_from {
+                    return Some(name);
+                }
+                name
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                let mut name = c.to_string();
+                while let Some(s) = chars.peek() {
+                    if s.is_alphanumeric() || *s == '_' || *s == '$' {
+                        name.push(*s);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if previous_was_from {
+                    return Some(name);
+                }
+                name
+            }
+            c if c.is_whitespace() => continue,
+            _ => {
+                previous_was_from = false;
+                continue;
+            }
+        };
+        previous_was_from = token.eq_ignore_ascii_case("from");
+    }
+    None
+}
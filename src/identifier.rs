@@ -1,9 +1,12 @@
-use crate::entity::Kind;
+use crate::deserialize::{DatastoreDeserializeError, Deserialize};
+use crate::entity::{DatastoreValue, Kind};
 use crate::error::DatastoreKeyError;
+use crate::serialize::{DatastoreSerializeError, Serialize};
 use crate::{DatastoreNameRepresentationError, DatastorersError};
 use google_datastore1::schemas;
 use google_datastore1::schemas::Key;
 use std::convert::TryFrom;
+use std::fmt::Debug;
 use std::marker::PhantomData;
 
 /// A KeyPath knows how to create a [Key](schemas::Key) from itself.
@@ -217,6 +220,28 @@ where
     }
 }
 
+/// Stored as a `key_value`, to reference another entity
+impl<T, Child> Serialize for IdentifierId<T, Child>
+where
+    T: Kind,
+    Child: KeyPathElement + PartialEq,
+{
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        serialize_key_path(&self)
+    }
+}
+
+/// Read from a `key_value`, the key must have the kind path of the identifier chain
+impl<T, Child> Deserialize for IdentifierId<T, Child>
+where
+    T: Kind + Debug,
+    Child: KeyPathElement + PartialEq + Debug,
+{
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        deserialize_key_path(value)
+    }
+}
+
 /// Must be implemented by types that are used as [IdentifierName](IdentifierName) representations.
 pub trait SerializeIdentifierName {
     fn to_string(&self) -> String;
@@ -404,6 +429,73 @@ where
     }
 }
 
+/// Stored as a `key_value`, to reference another entity
+impl<T, Representation, Child> Serialize for IdentifierName<T, Representation, Child>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    Child: KeyPathElement + PartialEq,
+{
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        serialize_key_path(&self)
+    }
+}
+
+/// Read from a `key_value`, the key must have the kind path of the identifier chain
+impl<T, Representation, Child> Deserialize for IdentifierName<T, Representation, Child>
+where
+    T: Kind + Debug,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq + Debug,
+    Child: KeyPathElement + PartialEq + Debug,
+{
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        deserialize_key_path(value)
+    }
+}
+
+fn serialize_key_path(
+    key_path: &impl KeyPath,
+) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+    let key = key_path.get_key();
+    // A reference must point to an existing entity, which always has a complete key
+    let incomplete = key
+        .path
+        .iter()
+        .flatten()
+        .any(|element| element.id.is_none() && element.name.is_none());
+    if incomplete {
+        return Err(DatastoreSerializeError::IncompleteKey);
+    }
+    let mut value = DatastoreValue::empty();
+    value.key_value = Some(key);
+    Ok(Some(value))
+}
+
+fn deserialize_key_path<K>(value: DatastoreValue) -> Result<K, DatastoreDeserializeError>
+where
+    K: KeyPath + TryFrom<Key, Error = DatastorersError>,
+{
+    let key = value
+        .0
+        .key_value
+        .ok_or(DatastoreDeserializeError::NoSuchValue)?;
+    let found = key.path.as_ref().map_or(0, Vec::len);
+    let key_path = K::try_from(key).map_err(|err| -> DatastoreDeserializeError {
+        match err {
+            DatastorersError::DatastoreKeyError(err) => err.into(),
+            DatastorersError::DatastoreNameRepresentationError(err) => err.into(),
+            // Key parsing only fails with key or name representation errors
+            _ => DatastoreKeyError::NoKey.into(),
+        }
+    })?;
+    // Parsing stops at the end of the identifier chain, longer keys are not of the expected kind path
+    let expected = key_path.get_key().path.map_or(0, |path| path.len());
+    if expected != found {
+        return Err(DatastoreKeyError::WrongKeyPathLength { expected, found }.into());
+    }
+    Ok(key_path)
+}
+
 /// This type is put as the last ancestor to finish of a full Key identifier path.
 /// IdentifierNone is the implicit Ancestor argument for [IdentifierId](IdentifierId) and
 /// [IdentifierName](IdentifierName).
@@ -454,6 +546,177 @@ impl KeyPathElement for IdentifierNone {
     }
 }
 
+/// The key path type with its last element removed, the last element of a root key path
+/// is replaced by [IdentifierNone](IdentifierNone).
+/// Used to find the [parent](ParentKeyPath) of a key path.
+pub trait TruncatedKeyPath {
+    type Truncated: KeyPathElement + PartialEq;
+}
+
+impl<T> TruncatedKeyPath for IdentifierId<T, IdentifierNone>
+where
+    T: Kind,
+{
+    type Truncated = IdentifierNone;
+}
+
+impl<T, U, Child> TruncatedKeyPath for IdentifierId<T, IdentifierId<U, Child>>
+where
+    T: Kind,
+    U: Kind,
+    Child: KeyPathElement + PartialEq,
+    IdentifierId<U, Child>: TruncatedKeyPath,
+{
+    type Truncated = IdentifierId<T, <IdentifierId<U, Child> as TruncatedKeyPath>::Truncated>;
+}
+
+impl<T, U, R, Child> TruncatedKeyPath for IdentifierId<T, IdentifierName<U, R, Child>>
+where
+    T: Kind,
+    U: Kind,
+    R: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    Child: KeyPathElement + PartialEq,
+    IdentifierName<U, R, Child>: TruncatedKeyPath,
+{
+    type Truncated = IdentifierId<T, <IdentifierName<U, R, Child> as TruncatedKeyPath>::Truncated>;
+}
+
+impl<T, Representation> TruncatedKeyPath for IdentifierName<T, Representation, IdentifierNone>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+{
+    type Truncated = IdentifierNone;
+}
+
+impl<T, Representation, U, Child> TruncatedKeyPath
+    for IdentifierName<T, Representation, IdentifierId<U, Child>>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    U: Kind,
+    Child: KeyPathElement + PartialEq,
+    IdentifierId<U, Child>: TruncatedKeyPath,
+{
+    type Truncated =
+        IdentifierName<T, Representation, <IdentifierId<U, Child> as TruncatedKeyPath>::Truncated>;
+}
+
+impl<T, Representation, U, R, Child> TruncatedKeyPath
+    for IdentifierName<T, Representation, IdentifierName<U, R, Child>>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    U: Kind,
+    R: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    Child: KeyPathElement + PartialEq,
+    IdentifierName<U, R, Child>: TruncatedKeyPath,
+{
+    type Truncated = IdentifierName<
+        T,
+        Representation,
+        <IdentifierName<U, R, Child> as TruncatedKeyPath>::Truncated,
+    >;
+}
+
+/// Implemented by key paths that have at least one ancestor, `Parent` is the key path
+/// of the closest ancestor. It is used by the `children_of` function generated by
+/// the `DatastoreManaged` derive.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_entity"]
+/// # struct MyEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// # }
+/// #[derive(DatastoreManaged)]
+/// #[kind="my_child_entity"]
+/// struct MyChildEntity {
+///     #[key]
+///     key: IdentifierId<MyEntity, IdentifierString<Self>>,
+/// }
+///
+/// // Query the children of the MyEntity with id 5
+/// let parent: IdentifierId<MyEntity> = id![5];
+/// let query = MyChildEntity::children_of(&parent);
+/// ```
+///
+/// Passing a key path that is not the parent of the entity fails to compile:
+/// ```compile_fail
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_entity"]
+/// # struct MyEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// # }
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_child_entity"]
+/// # struct MyChildEntity {
+/// #     #[key]
+/// #     key: IdentifierId<MyEntity, IdentifierString<Self>>,
+/// # }
+/// let not_the_parent: IdentifierString<MyChildEntity> = name!["child"];
+/// let query = MyChildEntity::children_of(&not_the_parent);
+/// ```
+pub trait ParentKeyPath {
+    type Parent: KeyPathElement + PartialEq;
+}
+
+impl<T, U, Child> ParentKeyPath for IdentifierId<T, IdentifierId<U, Child>>
+where
+    T: Kind,
+    U: Kind,
+    Child: KeyPathElement + PartialEq,
+    Self: TruncatedKeyPath,
+{
+    type Parent = <Self as TruncatedKeyPath>::Truncated;
+}
+
+impl<T, U, R, Child> ParentKeyPath for IdentifierId<T, IdentifierName<U, R, Child>>
+where
+    T: Kind,
+    U: Kind,
+    R: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    Child: KeyPathElement + PartialEq,
+    Self: TruncatedKeyPath,
+{
+    type Parent = <Self as TruncatedKeyPath>::Truncated;
+}
+
+impl<T, Representation, U, Child> ParentKeyPath
+    for IdentifierName<T, Representation, IdentifierId<U, Child>>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    U: Kind,
+    Child: KeyPathElement + PartialEq,
+    Self: TruncatedKeyPath,
+{
+    type Parent = <Self as TruncatedKeyPath>::Truncated;
+}
+
+impl<T, Representation, U, R, Child> ParentKeyPath
+    for IdentifierName<T, Representation, IdentifierName<U, R, Child>>
+where
+    T: Kind,
+    Representation: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    U: Kind,
+    R: SerializeIdentifierName + DeserializeIdentifierName + PartialEq,
+    Child: KeyPathElement + PartialEq,
+    Self: TruncatedKeyPath,
+{
+    type Parent = <Self as TruncatedKeyPath>::Truncated;
+}
+
+/// Implemented by the [parent](ParentKeyPath) key path of the key path `K`.
+pub trait ParentKeyPathOf<K> {}
+
+impl<K> ParentKeyPathOf<K> for <K as ParentKeyPath>::Parent where K: ParentKeyPath {}
+
 /// A macro for simplifying the creation of a chain of [IdentifierId](IdentifierId),
 /// [IdentifierName](IdentifierName), or [IdentifierNone](IdentifierNone) structs.
 ///
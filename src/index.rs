@@ -0,0 +1,514 @@
+use crate::error::DatastoreIndexError;
+use crate::query::{Order, KEY_PROPERTY};
+use google_datastore1::schemas::{
+    Filter, PropertyFilterOp, PropertyOrder, PropertyOrderDirection, Query,
+};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A property of a composite index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProperty {
+    /// Property name in the datastore entity
+    pub name: String,
+    pub direction: Order,
+}
+
+/// A composite index, as declared in an `index.yaml` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompositeIndex {
+    pub kind: String,
+    /// If the index supports ancestor queries
+    pub ancestor: bool,
+    pub properties: Vec<IndexProperty>,
+}
+
+impl CompositeIndex {
+    pub fn new(kind: &str, ancestor: bool, properties: Vec<(&str, Order)>) -> Self {
+        CompositeIndex {
+            kind: kind.to_string(),
+            ancestor,
+            properties: properties
+                .into_iter()
+                .map(|(name, direction)| IndexProperty {
+                    name: name.to_string(),
+                    direction,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Composite indexes declared on an entity.
+///
+/// Implemented by the `DatastoreManaged` derive, indexes are declared with one
+/// `#[index(...)]` attribute per index on the struct. Properties are referenced by
+/// their field name, followed by the direction, `asc` or `desc`. Add `ancestor`
+/// for indexes used by ancestor queries.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # use datastorers::index::DatastoreIndexes;
+/// #[derive(DatastoreManaged)]
+/// #[kind = "Task"]
+/// #[index(properties(done = "asc", priority = "desc"))]
+/// #[index(ancestor, properties(priority = "desc"))]
+/// struct Task {
+///     #[key]
+///     key: IdentifierId<Self>,
+///     done: bool,
+///     #[property = "Priority"]
+///     priority: i64,
+/// }
+///
+/// assert_eq!(2, Task::indexes().len());
+/// assert_eq!("Priority", Task::indexes()[1].properties[0].name);
+/// ```
+pub trait DatastoreIndexes {
+    fn indexes() -> Vec<CompositeIndex>;
+}
+
+/// Builds a merged `index.yaml` from the composite indexes of several entities.
+/// Indexes declared more than once, e.g. by two structs of the same kind, are only included once.
+///
+/// **Every entity must be added with [entity](IndexYaml::entity).** Declared indexes are not
+/// collected automatically, the indexes of an entity that is not added are missing from the
+/// generated `index.yaml`, and queries needing them fail when they are run. Keep the list of
+/// entities in one place, next to the entity definitions.
+///
+/// Example:
+/// ```no_run
+/// # use datastorers::*;
+/// # use datastorers::index::IndexYaml;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind = "Task"]
+/// # #[index(properties(done = "asc", priority = "desc"))]
+/// # struct Task {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     done: bool,
+/// #     priority: i64,
+/// # }
+/// let index_yaml = IndexYaml::new().entity::<Task>().to_string();
+/// std::fs::write("index.yaml", index_yaml)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IndexYaml {
+    indexes: Vec<CompositeIndex>,
+}
+
+impl IndexYaml {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the indexes declared on the entity `E`, this must be called for every entity
+    /// declaring indexes
+    pub fn entity<E: DatastoreIndexes>(self) -> Self {
+        self.indexes(E::indexes())
+    }
+
+    /// Add indexes that are not declared on an entity
+    pub fn indexes(mut self, indexes: Vec<CompositeIndex>) -> Self {
+        for index in indexes {
+            if !self.indexes.contains(&index) {
+                self.indexes.push(index);
+            }
+        }
+        self
+    }
+
+    pub fn get_indexes(&self) -> &[CompositeIndex] {
+        &self.indexes
+    }
+
+    /// Check if the query can be served by the built-in indexes, or else by one of the
+    /// composite indexes in this index.yaml.
+    ///
+    /// The check is conservative, only the filters, the ancestor and the sort orders of the
+    /// query are considered and a matching composite index must serve the query on its own.
+    ///
+    /// Example:
+    /// ```
+    /// # use datastorers::*;
+    /// # use datastorers::index::{IndexCoverage, IndexYaml};
+    /// # use google_datastore1::schemas::Query;
+    /// # use std::convert::TryInto;
+    /// # #[derive(DatastoreManaged)]
+    /// # #[kind = "Task"]
+    /// # struct Task {
+    /// #     #[key]
+    /// #     key: IdentifierId<Self>,
+    /// #     done: bool,
+    /// #     priority: i64,
+    /// # }
+    /// let index_yaml: IndexYaml = r#"
+    /// indexes:
+    /// - kind: "Task"
+    ///   properties:
+    ///   - name: "done"
+    ///   - name: "priority"
+    ///     direction: desc
+    /// "#
+    /// .parse()?;
+    ///
+    /// let query: Query = Task::query()
+    ///     .filter(Task::DONE, Operator::Equal, false)?
+    ///     .order_by(Task::PRIORITY, Order::Descending)
+    ///     .try_into()?;
+    /// assert!(index_yaml.coverage(&query).is_covered());
+    /// # Ok::<(), DatastorersError>(())
+    /// ```
+    pub fn coverage(&self, query: &Query) -> IndexCoverage {
+        let required = match required_index(query) {
+            Some(required) => required,
+            None => return IndexCoverage::BuiltIn,
+        };
+        match self
+            .indexes
+            .iter()
+            .find(|index| required.is_served_by(index))
+        {
+            Some(index) => IndexCoverage::Composite(index.clone()),
+            None => IndexCoverage::Missing(required.into()),
+        }
+    }
+}
+
+impl FromStr for IndexYaml {
+    type Err = DatastoreIndexError;
+
+    /// Parse an index.yaml file, in the block style used by the `gcloud` tool
+    fn from_str(index_yaml: &str) -> Result<Self, Self::Err> {
+        let mut indexes: Vec<CompositeIndex> = Vec::new();
+        for (line_index, raw_line) in index_yaml.lines().enumerate() {
+            let line = line_index + 1;
+            let invalid = |reason| DatastoreIndexError::InvalidIndexYaml { line, reason };
+
+            let content = strip_comment(raw_line).trim();
+            if content.is_empty() {
+                continue;
+            }
+            let (list_item, content) = match content.strip_prefix('-') {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, content),
+            };
+            if content.is_empty() {
+                return Err(invalid("empty list item"));
+            }
+            let colon = content
+                .find(':')
+                .ok_or_else(|| invalid("expected key: value"))?;
+            let key = content[..colon].trim();
+            let value = yaml_value(content[colon + 1..].trim());
+
+            match (key, list_item) {
+                ("indexes", false) => (),
+                ("kind", _) => {
+                    if list_item {
+                        indexes.push(CompositeIndex::new(value, false, vec![]));
+                    } else {
+                        let index = indexes
+                            .last_mut()
+                            .ok_or_else(|| invalid("kind outside of index"))?;
+                        index.kind = value.to_string();
+                    }
+                }
+                ("ancestor", false) => {
+                    let index = indexes
+                        .last_mut()
+                        .ok_or_else(|| invalid("ancestor outside of index"))?;
+                    index.ancestor = match value {
+                        "yes" | "true" => true,
+                        "no" | "false" => false,
+                        _ => return Err(invalid("ancestor must be yes or no")),
+                    };
+                }
+                ("properties", false) => (),
+                ("name", true) => {
+                    let index = indexes
+                        .last_mut()
+                        .ok_or_else(|| invalid("property outside of index"))?;
+                    index.properties.push(IndexProperty {
+                        name: value.to_string(),
+                        direction: Order::Ascending,
+                    });
+                }
+                ("direction", false) => {
+                    let property = indexes
+                        .last_mut()
+                        .and_then(|index| index.properties.last_mut())
+                        .ok_or_else(|| invalid("direction outside of property"))?;
+                    property.direction = match value {
+                        "asc" => Order::Ascending,
+                        "desc" => Order::Descending,
+                        _ => return Err(invalid("direction must be asc or desc")),
+                    };
+                }
+                _ => return Err(invalid("unexpected key")),
+            }
+        }
+        if indexes.iter().any(|index| index.kind.is_empty()) {
+            return Err(DatastoreIndexError::InvalidIndexYaml {
+                line: 0,
+                reason: "index without kind",
+            });
+        }
+        Ok(IndexYaml::new().indexes(indexes))
+    }
+}
+
+impl Display for IndexYaml {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "indexes:")?;
+        for index in &self.indexes {
+            writeln!(f, "- kind: {}", yaml_string(&index.kind))?;
+            if index.ancestor {
+                writeln!(f, "  ancestor: yes")?;
+            }
+            writeln!(f, "  properties:")?;
+            for property in &index.properties {
+                writeln!(f, "  - name: {}", yaml_string(&property.name))?;
+                let direction = match property.direction {
+                    Order::Ascending => "asc",
+                    Order::Descending => "desc",
+                };
+                writeln!(f, "    direction: {}", direction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Remove a trailing comment, `#` inside quoted values is kept
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn yaml_value(value: &str) -> &str {
+    let unquoted = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')));
+    unquoted.unwrap_or(value)
+}
+
+/// Result of checking a query against the indexes of an index.yaml
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexCoverage {
+    /// The query is served by the built-in single property indexes
+    BuiltIn,
+    /// The query needs a composite index, and the contained index serves it
+    Composite(CompositeIndex),
+    /// The query needs a composite index that is missing, the contained index would serve it
+    Missing(CompositeIndex),
+}
+
+impl IndexCoverage {
+    /// If the query can run without adding an index
+    pub fn is_covered(&self) -> bool {
+        !matches!(self, IndexCoverage::Missing(_))
+    }
+
+    /// If the query needs a composite index, regardless of it being declared or not
+    pub fn needs_composite_index(&self) -> bool {
+        !matches!(self, IndexCoverage::BuiltIn)
+    }
+}
+
+/// The composite index needed by a query. Properties with equality filters may appear
+/// in any order and direction in the index, followed by the sort order properties.
+struct RequiredIndex {
+    kind: String,
+    ancestor: bool,
+    equality_properties: Vec<String>,
+    ordered_properties: Vec<IndexProperty>,
+}
+
+impl RequiredIndex {
+    fn is_served_by(&self, index: &CompositeIndex) -> bool {
+        let equality_count = self.equality_properties.len();
+        if index.kind != self.kind
+            || index.ancestor != self.ancestor
+            || index.properties.len() != equality_count + self.ordered_properties.len()
+        {
+            return false;
+        }
+        let (equality, ordered) = index.properties.split_at(equality_count);
+        let equality_match = equality
+            .iter()
+            .all(|property| self.equality_properties.contains(&property.name));
+        // Indexes can be scanned in reverse
+        let same_direction = ordered
+            .iter()
+            .zip(&self.ordered_properties)
+            .all(|(a, b)| a == b);
+        let reversed_direction = ordered
+            .iter()
+            .zip(&self.ordered_properties)
+            .all(|(a, b)| a.name == b.name && a.direction != b.direction);
+        equality_match && (same_direction || reversed_direction)
+    }
+}
+
+impl From<RequiredIndex> for CompositeIndex {
+    fn from(required: RequiredIndex) -> Self {
+        let mut properties: Vec<IndexProperty> = required
+            .equality_properties
+            .into_iter()
+            .map(|name| IndexProperty {
+                name,
+                direction: Order::Ascending,
+            })
+            .collect();
+        properties.extend(required.ordered_properties);
+        CompositeIndex {
+            kind: required.kind,
+            ancestor: required.ancestor,
+            properties,
+        }
+    }
+}
+
+/// Find the composite index needed by the query, `None` if the built-in indexes are enough
+fn required_index(query: &Query) -> Option<RequiredIndex> {
+    let kind = query
+        .kind
+        .as_ref()
+        .and_then(|kinds| kinds.first())
+        .and_then(|kind| kind.name.clone())?;
+
+    let mut ancestor = false;
+    let mut equality_properties: Vec<String> = Vec::new();
+    let mut inequality_properties: Vec<String> = Vec::new();
+    let mut property_filters = vec![];
+    collect_property_filters(query.filter.as_ref(), &mut property_filters);
+    for (name, op) in property_filters {
+        match op {
+            PropertyFilterOp::HasAncestor => ancestor = true,
+            PropertyFilterOp::Equal => {
+                if !equality_properties.contains(&name) {
+                    equality_properties.push(name);
+                }
+            }
+            _ => {
+                if !inequality_properties.contains(&name) {
+                    inequality_properties.push(name);
+                }
+            }
+        }
+    }
+
+    // Sort orders on properties with equality filters are ignored by datastore,
+    // as is a trailing ascending key order since entities are always ordered by key last
+    let mut orders: Vec<IndexProperty> = query
+        .order
+        .iter()
+        .flatten()
+        .filter_map(index_property)
+        .filter(|order| !equality_properties.contains(&order.name))
+        .collect();
+    if orders
+        .last()
+        .map(|order| order.name == KEY_PROPERTY && order.direction == Order::Ascending)
+        .unwrap_or(false)
+    {
+        orders.pop();
+    }
+
+    let key_inequality_only = inequality_properties.iter().all(|p| p == KEY_PROPERTY);
+    let built_in = match (equality_properties.is_empty(), inequality_properties.len()) {
+        // No filters and at most one sort order, or an ancestor filter without sort orders
+        (true, 0) => match orders.as_slice() {
+            [] => true,
+            [order] => {
+                !ancestor && (order.name != KEY_PROPERTY || order.direction == Order::Ascending)
+            }
+            _ => false,
+        },
+        // Inequality filters on a single property, optionally sorted by that property
+        (true, 1) => {
+            (!ancestor || key_inequality_only)
+                && orders
+                    .iter()
+                    .all(|order| order.name == inequality_properties[0])
+        }
+        // Equality filters, optionally with an ancestor and key inequality filters, merged
+        // from the built-in indexes
+        (false, _) => key_inequality_only && orders.is_empty(),
+        _ => false,
+    };
+    if built_in {
+        return None;
+    }
+
+    // The property with inequality filters must be the first sort order
+    let mut ordered_properties = orders;
+    for inequality_property in inequality_properties {
+        if !ordered_properties
+            .iter()
+            .any(|order| order.name == inequality_property)
+        {
+            ordered_properties.insert(
+                0,
+                IndexProperty {
+                    name: inequality_property,
+                    direction: Order::Ascending,
+                },
+            );
+        }
+    }
+    equality_properties.sort();
+
+    Some(RequiredIndex {
+        kind,
+        ancestor,
+        equality_properties,
+        ordered_properties,
+    })
+}
+
+fn collect_property_filters(filter: Option<&Filter>, into: &mut Vec<(String, PropertyFilterOp)>) {
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return,
+    };
+    if let Some(property_filter) = &filter.property_filter {
+        let name = property_filter
+            .property
+            .as_ref()
+            .and_then(|property| property.name.clone());
+        if let (Some(name), Some(op)) = (name, property_filter.op) {
+            into.push((name, op));
+        }
+    }
+    if let Some(composite_filter) = &filter.composite_filter {
+        for filter in composite_filter.filters.iter().flatten() {
+            collect_property_filters(Some(filter), into);
+        }
+    }
+}
+
+fn index_property(order: &PropertyOrder) -> Option<IndexProperty> {
+    let name = order.property.as_ref()?.name.clone()?;
+    let direction = match order.direction {
+        Some(PropertyOrderDirection::Descending) => Order::Descending,
+        _ => Order::Ascending,
+    };
+    Some(IndexProperty { name, direction })
+}
@@ -3,25 +3,40 @@
 #![warn(unused)]
 #![warn(rustdoc)]
 
+pub use crate::aggregate::Summable;
 pub use crate::connection::DatastoreConnection;
 pub use crate::entity::{
-    DatastoreEntity, DatastoreEntityCollection, DatastoreProperties, DatastoreValue, Kind, Pagable,
-    ResultCollection,
+    DatastoreEntity, DatastoreEntityCollection, DatastoreProperties, DatastoreValue, Keyed, Kind,
+    Pagable, ResultCollection,
 };
 pub use crate::error::*;
+pub use crate::gql::*;
 pub use crate::identifier::*;
+pub use crate::property::*;
 pub use crate::query::*;
+pub use crate::query_display::*;
 pub use crate::update::*;
 
-pub use datastore_entity_derives::DatastoreManaged;
+pub use datastore_entity_derives::{DatastoreEmbedded, DatastoreEnum, DatastoreManaged};
 
+mod aggregate;
 pub mod bytes;
+pub mod cache;
 pub mod connection;
 pub mod deserialize;
 mod entity;
 pub mod error;
+pub mod geo;
+pub mod gql;
 mod identifier;
+pub mod index;
+pub mod metadata;
+mod property;
 pub mod query;
+mod query_display;
+pub mod scan;
+#[cfg(feature = "serde")]
+pub mod serde_value;
 pub mod serialize;
 pub mod transaction;
 pub mod update;
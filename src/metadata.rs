@@ -0,0 +1,175 @@
+use crate::connection::DatastoreConnection;
+use crate::deserialize::Deserialize;
+use crate::entity::DatastoreValue;
+use crate::error::{DatastoreKeyError, DatastorersError};
+use crate::query::{QueryBatches, KEY_PROPERTY};
+
+use google_datastore1::schemas::{
+    EntityResult, Filter, Key, KindExpression, PathElement, Projection, PropertyFilter,
+    PropertyFilterOp, PropertyReference, Query,
+};
+
+const KIND_KIND: &str = "__kind__";
+const PROPERTY_KIND: &str = "__property__";
+const NAMESPACE_KIND: &str = "__namespace__";
+const PROPERTY_REPRESENTATION: &str = "property_representation";
+
+/// A property of a kind, as reported by a `__property__` metadata query
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyMetadata {
+    /// The kind that has the property
+    pub kind: String,
+    /// Name of the property
+    pub name: String,
+    /// Representations of the values stored in the property, e.g. `STRING`, `INT64` or `BOOLEAN`
+    pub representations: Vec<String>,
+}
+
+/// Get the names of all kinds in the project, using a `__kind__`
+/// [metadata query](https://cloud.google.com/datastore/docs/concepts/metadataqueries)
+pub async fn kinds(connection: &impl DatastoreConnection) -> Result<Vec<String>, DatastorersError> {
+    let results = fetch_all(connection, metadata_query(KIND_KIND, true, None)).await?;
+    results
+        .iter()
+        .map(|result| {
+            let element = last_path_element(result)?;
+            element
+                .name
+                .clone()
+                .ok_or_else(|| DatastoreKeyError::ExpectedName.into())
+        })
+        .collect()
+}
+
+/// Get the properties of all kinds in the project
+pub async fn properties(
+    connection: &impl DatastoreConnection,
+) -> Result<Vec<PropertyMetadata>, DatastorersError> {
+    let results = fetch_all(connection, metadata_query(PROPERTY_KIND, false, None)).await?;
+    results.into_iter().map(property_metadata).collect()
+}
+
+/// Get the properties of one kind
+pub async fn properties_of_kind(
+    connection: &impl DatastoreConnection,
+    kind: &str,
+) -> Result<Vec<PropertyMetadata>, DatastorersError> {
+    let kind_key = Key {
+        partition_id: None,
+        path: Some(vec![PathElement {
+            id: None,
+            kind: Some(KIND_KIND.to_string()),
+            name: Some(kind.to_string()),
+        }]),
+    };
+    let results = fetch_all(
+        connection,
+        metadata_query(PROPERTY_KIND, false, Some(kind_key)),
+    )
+    .await?;
+    results.into_iter().map(property_metadata).collect()
+}
+
+/// Get the names of all namespaces in the project, the default namespace is named `""`
+pub async fn namespaces(
+    connection: &impl DatastoreConnection,
+) -> Result<Vec<String>, DatastorersError> {
+    let results = fetch_all(connection, metadata_query(NAMESPACE_KIND, true, None)).await?;
+    results
+        .iter()
+        .map(|result| {
+            let element = last_path_element(result)?;
+            match (&element.name, element.id) {
+                (Some(name), _) => Ok(name.clone()),
+                // The default namespace is identified by the id 1
+                (None, Some(_)) => Ok(String::new()),
+                (None, None) => Err(DatastoreKeyError::ExpectedName.into()),
+            }
+        })
+        .collect()
+}
+
+fn metadata_query(kind: &str, keys_only: bool, ancestor: Option<Key>) -> Query {
+    let projection = match keys_only {
+        true => Some(vec![Projection {
+            property: Some(PropertyReference {
+                name: Some(String::from(KEY_PROPERTY)),
+            }),
+        }]),
+        false => None,
+    };
+    let filter = ancestor.map(|key| {
+        let mut value = DatastoreValue::empty();
+        value.key_value = Some(key);
+        Filter {
+            property_filter: Some(PropertyFilter {
+                property: Some(PropertyReference {
+                    name: Some(String::from(KEY_PROPERTY)),
+                }),
+                op: Some(PropertyFilterOp::HasAncestor),
+                value: Some(value.into()),
+            }),
+            composite_filter: None,
+        }
+    });
+    Query {
+        kind: Some(vec![KindExpression {
+            name: Some(kind.to_string()),
+        }]),
+        projection,
+        filter,
+        ..Default::default()
+    }
+}
+
+async fn fetch_all(
+    connection: &impl DatastoreConnection,
+    query: Query,
+) -> Result<Vec<EntityResult>, DatastorersError> {
+    let mut batches = QueryBatches::new(query);
+    let mut results = vec![];
+    while let Some(batch) = batches.next_batch(connection).await? {
+        results.extend(batch);
+    }
+    Ok(results)
+}
+
+fn key_path(result: &EntityResult) -> Result<&Vec<PathElement>, DatastorersError> {
+    let key = result
+        .entity
+        .as_ref()
+        .and_then(|entity| entity.key.as_ref())
+        .ok_or(DatastoreKeyError::NoKey)?;
+    Ok(key.path.as_ref().ok_or(DatastoreKeyError::NoKeyPath)?)
+}
+
+fn last_path_element(result: &EntityResult) -> Result<&PathElement, DatastorersError> {
+    let path = key_path(result)?;
+    Ok(path.last().ok_or(DatastoreKeyError::NoKeyPathElement)?)
+}
+
+fn property_metadata(result: EntityResult) -> Result<PropertyMetadata, DatastorersError> {
+    // The key of a property entity is the kind as parent and the property name as child
+    let path = key_path(&result)?;
+    let kind = path
+        .first()
+        .and_then(|element| element.name.clone())
+        .ok_or(DatastoreKeyError::ExpectedName)?;
+    let name = path
+        .last()
+        .and_then(|element| element.name.clone())
+        .ok_or(DatastoreKeyError::ExpectedName)?;
+    let representations = match result
+        .entity
+        .and_then(|entity| entity.properties)
+        .and_then(|mut properties| properties.remove(PROPERTY_REPRESENTATION))
+    {
+        Some(value) => Vec::<String>::deserialize(DatastoreValue(value))?,
+        None => vec![],
+    };
+    Ok(PropertyMetadata {
+        kind,
+        name,
+        representations,
+    })
+}
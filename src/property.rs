@@ -0,0 +1,179 @@
+use crate::serialize::Serialize;
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+
+/// A typed reference to a property of the entity `E`, holding values of type `T`.
+///
+/// Property descriptors are generated by the `DatastoreManaged` derive, one associated
+/// constant per field, named after the field in upper case. `T` is the type of the
+/// individual values of the property, i.e. `Option` and `Vec` wrappers are removed
+/// from the field type.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// #[derive(DatastoreManaged)]
+/// #[kind="my_entity"]
+/// struct MyEntity {
+///     #[key]
+///     key: IdentifierId<Self>,
+///     #[property = "Name"]
+///     name: String,
+///     tags: Vec<String>,
+/// }
+///
+/// let name: Property<MyEntity, String> = MyEntity::NAME;
+/// assert_eq!("Name", name.name());
+/// let tags: Property<MyEntity, String> = MyEntity::TAGS;
+/// assert_eq!("tags", tags.name());
+/// ```
+///
+/// Filtering with a value of the wrong type fails to compile:
+/// ```compile_fail
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_entity"]
+/// # struct MyEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     name: String,
+/// # }
+/// let query = MyEntity::query().filter(MyEntity::NAME, Operator::Equal, 5);
+/// ```
+///
+/// Filtering or ordering on a property of another entity fails to compile:
+/// ```compile_fail
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_entity"]
+/// # struct MyEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     name: String,
+/// # }
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="other_entity"]
+/// # struct OtherEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     name: String,
+/// # }
+/// let query = MyEntity::query().filter(OtherEntity::NAME, Operator::Equal, "name".to_string());
+/// ```
+/// ```compile_fail
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="my_entity"]
+/// # struct MyEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     name: String,
+/// # }
+/// # #[derive(DatastoreManaged)]
+/// # #[kind="other_entity"]
+/// # struct OtherEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     name: String,
+/// # }
+/// let query = MyEntity::query().order_by(OtherEntity::NAME, Order::Ascending);
+/// ```
+pub struct Property<E, T> {
+    name: Cow<'static, str>,
+    phantom: PhantomData<fn() -> (E, T)>,
+}
+
+impl<E, T> Property<E, T> {
+    pub const fn new(name: &'static str) -> Self {
+        Property {
+            name: Cow::Borrowed(name),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The name of the property in the datastore entity
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A property of the embedded entity stored in this property.
+    /// The name of the nested property is the dotted path, e.g. `address.city`.
+    ///
+    /// Example:
+    /// ```
+    /// # use datastorers::*;
+    /// #[derive(DatastoreEmbedded, Debug)]
+    /// struct Address {
+    ///     #[property = "City"]
+    ///     city: String,
+    /// }
+    ///
+    /// #[derive(DatastoreManaged)]
+    /// #[kind = "Person"]
+    /// struct Person {
+    ///     #[key]
+    ///     key: IdentifierId<Self>,
+    ///     addresses: Vec<Address>,
+    /// }
+    ///
+    /// let city: Property<Person, String> = Person::ADDRESSES.nested(Address::CITY);
+    /// assert_eq!("addresses.City", city.name());
+    /// ```
+    pub fn nested<U>(&self, property: Property<T, U>) -> Property<E, U> {
+        Property {
+            name: Cow::Owned(format!("{}.{}", self.name, property.name)),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, T> Clone for Property<E, T> {
+    fn clone(&self) -> Self {
+        Property {
+            name: self.name.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, T> Debug for Property<E, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Property").field(&self.name).finish()
+    }
+}
+
+/// Something that names a property of the entity `E` in a query.
+/// Implemented by [Property](Property) and, untyped, by `String`.
+pub trait QueryProperty<E> {
+    fn into_property_name(self) -> String;
+}
+
+/// Something that names a property of the entity `E` that can be compared to values of type `V`.
+/// [Property](Property) only accepts values of its own value type,
+/// while a `String` property name accepts any serializable value.
+pub trait FilterProperty<E, V>: QueryProperty<E> {}
+
+impl<E> QueryProperty<E> for String {
+    fn into_property_name(self) -> String {
+        self
+    }
+}
+
+impl<E, V: Serialize> FilterProperty<E, V> for String {}
+
+impl<E, T> QueryProperty<E> for Property<E, T> {
+    fn into_property_name(self) -> String {
+        self.name.into_owned()
+    }
+}
+
+impl<E, T> FilterProperty<E, T> for Property<E, T> {}
+
+impl<E, T> QueryProperty<E> for &Property<E, T> {
+    fn into_property_name(self) -> String {
+        self.name.to_string()
+    }
+}
+
+impl<E, T> FilterProperty<E, T> for &Property<E, T> {}
@@ -1,25 +1,32 @@
 use std::convert::TryFrom;
 use std::convert::TryInto;
+use std::fmt::{Display, Formatter};
 use std::marker::PhantomData;
 
 use async_trait::async_trait;
 
+use crate::cache::readable_cache;
 use crate::connection::DatastoreConnection;
 use crate::entity::{
-    DatastoreEntity, DatastoreEntityCollection, DatastoreValue, Kind, Pagable, ResultCollection,
+    DatastoreEntity, DatastoreEntityCollection, DatastoreValue, Keyed, Kind, Pagable,
+    ResultCollection,
 };
 use crate::error::{DatastoreClientError, DatastorersError};
 use crate::identifier::KeyPath;
+use crate::property::{FilterProperty, QueryProperty};
+use crate::query_display::QueryDisplay;
 
 use crate::serialize::{DatastoreSerializeError, Serialize};
 
 use google_datastore1::schemas::{
-    CompositeFilter, CompositeFilterOp, Filter, Key, KindExpression, LookupRequest, LookupResponse,
-    PropertyFilter, PropertyFilterOp, PropertyOrder, PropertyOrderDirection, PropertyReference,
-    Query, QueryResultBatchMoreResults, ReadOptions, RunQueryRequest, RunQueryResponse,
+    CompositeFilter, CompositeFilterOp, EntityResult, Filter, Key, KindExpression, LookupRequest,
+    LookupResponse, Projection, PropertyFilter, PropertyFilterOp, PropertyOrder,
+    PropertyOrderDirection, PropertyReference, Query, QueryResultBatch,
+    QueryResultBatchMoreResults, ReadOptions, RunQueryRequest, RunQueryResponse,
 };
 
 const DEFAULT_PAGE_SIZE: i32 = 50;
+pub(crate) const KEY_PROPERTY: &str = "__key__";
 
 #[async_trait]
 pub trait DatastorersQueryable<E>
@@ -66,16 +73,51 @@ where
     }
 }
 
+impl<E> Clone for DatastorersQuery<E>
+where
+    E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    fn clone(&self) -> Self {
+        DatastorersQuery {
+            entity: PhantomData,
+            filter: self.filter.clone(),
+            limit: self.limit,
+            order: self.order.clone(),
+        }
+    }
+}
+
+impl<E> Display for DatastorersQuery<E>
+where
+    E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.display().fmt(f)
+    }
+}
+
 impl<E> DatastorersQuery<E>
 where
     E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
 {
-    pub fn filter(
+    /// Render the query as a GQL-like string, see [QueryDisplay](QueryDisplay)
+    pub fn display(&self) -> QueryDisplay<'static> {
+        // Conversion only fails for an empty filter, which is never built by the query methods
+        let query = Query::try_from(self.clone()).unwrap_or_default();
+        QueryDisplay::from(query)
+    }
+
+    pub fn filter<P, V>(
         mut self,
-        property_name: String,
+        property: P,
         operator: Operator,
-        value: impl Serialize,
-    ) -> Result<DatastorersQuery<E>, DatastorersError> {
+        value: V,
+    ) -> Result<DatastorersQuery<E>, DatastorersError>
+    where
+        P: FilterProperty<E, V>,
+        V: Serialize,
+    {
+        let property_name = property.into_property_name();
         let ds_value = value
             .serialize()?
             .ok_or(DatastoreSerializeError::NoValueError)?;
@@ -93,25 +135,78 @@ where
         Ok(self)
     }
 
+    /// Filter on string values starting with `prefix`, as a range filter on the property.
+    /// The same restrictions as for other inequality filters apply.
+    pub fn starts_with<P>(
+        self,
+        property: P,
+        prefix: &str,
+    ) -> Result<DatastorersQuery<E>, DatastorersError>
+    where
+        P: FilterProperty<E, String> + Clone,
+    {
+        // Strings are ordered by their UTF-8 bytes, no character sorts after char::MAX
+        let upper_bound = format!("{}{}", prefix, char::MAX);
+        self.filter(
+            property.clone(),
+            Operator::GreaterThanOrEqual,
+            prefix.to_string(),
+        )?
+        .filter(property, Operator::LessThan, upper_bound)
+    }
+
     pub fn ancestor(
-        mut self,
+        self,
         key_path: &impl KeyPath,
     ) -> Result<DatastorersQuery<E>, DatastorersError> {
+        Ok(self.key_filter(Operator::HasAncestor, key_path.get_key()))
+    }
+
+    /// A projection query on `property` over all results, without sort orders and with the
+    /// default batch size. Queries with a limit are rejected, since the limit would only
+    /// set the batch size and not the number of results.
+    pub(crate) fn into_projection_query(self, property: String) -> Result<Query, DatastorersError> {
+        if self.limit.is_some() {
+            return Err(DatastoreClientError::AggregateWithLimit.into());
+        }
+        let mut query: Query = self.try_into()?;
+        query.limit = None;
+        query.order = None;
+        query.projection = Some(vec![Projection {
+            property: Some(PropertyReference {
+                name: Some(property),
+            }),
+        }]);
+        Ok(query)
+    }
+
+    /// True if the query is sorted by other properties than the key
+    pub(crate) fn has_property_order(&self) -> bool {
+        self.order.iter().any(|order| {
+            order
+                .property
+                .as_ref()
+                .and_then(|property| property.name.as_deref())
+                != Some(KEY_PROPERTY)
+        })
+    }
+
+    pub(crate) fn key_filter(mut self, operator: Operator, key: Key) -> DatastorersQuery<E> {
         let mut value = DatastoreValue::empty();
-        value.key_value = Some(key_path.get_key());
+        value.key_value = Some(key);
 
         match self.filter {
             Some(ref mut filter) => {
-                filter.push(String::from("__key__"), Operator::HasAncestor, value);
+                filter.push(String::from(KEY_PROPERTY), operator, value);
             }
             None => {
                 let mut filter = DatastorersPropertyFilter::default();
-                filter.push(String::from("__key__"), Operator::HasAncestor, value);
+                filter.push(String::from(KEY_PROPERTY), operator, value);
                 self.filter = Some(filter);
             }
         };
 
-        Ok(self)
+        self
     }
 
     pub fn limit(mut self, limit: i32) -> DatastorersQuery<E> {
@@ -120,10 +215,14 @@ where
         self
     }
 
-    pub fn order_by(mut self, property_name: String, order: Order) -> DatastorersQuery<E> {
+    pub fn order_by(
+        mut self,
+        property: impl QueryProperty<E>,
+        order: Order,
+    ) -> DatastorersQuery<E> {
         self.order.push(PropertyOrder {
             property: Some(PropertyReference {
-                name: Some(property_name),
+                name: Some(property.into_property_name()),
             }),
             direction: Some(order.into()),
         });
@@ -185,6 +284,29 @@ where
     }
 }
 
+impl<E> DatastorersQuery<E>
+where
+    E: Kind + Keyed + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    /// Only include entities with keys from `start` (inclusive) up to `end` (exclusive).
+    /// Keys are ordered by their path, element by element, with ids before names.
+    pub fn key_range(self, start: &E::Key, end: &E::Key) -> DatastorersQuery<E> {
+        self.key_filter(Operator::GreaterThanOrEqual, start.get_key())
+            .key_filter(Operator::LessThan, end.get_key())
+    }
+
+    pub fn order_by_key(mut self, order: Order) -> DatastorersQuery<E> {
+        self.order.push(PropertyOrder {
+            property: Some(PropertyReference {
+                name: Some(String::from(KEY_PROPERTY)),
+            }),
+            direction: Some(order.into()),
+        });
+
+        self
+    }
+}
+
 impl<E> TryFrom<DatastorersQuery<E>> for Query
 where
     E: Kind + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
@@ -237,6 +359,7 @@ impl From<Operator> for PropertyFilterOp {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Order {
     Ascending,
     Descending,
@@ -251,6 +374,7 @@ impl From<Order> for PropertyOrderDirection {
     }
 }
 
+#[derive(Clone)]
 pub struct DatastorersPropertyFilterItem {
     pub value: DatastoreValue,
     pub operator: Operator,
@@ -274,6 +398,7 @@ impl From<DatastorersPropertyFilterItem> for Filter {
     }
 }
 
+#[derive(Clone)]
 pub struct DatastorersPropertyFilter {
     pub filter_items: Vec<DatastorersPropertyFilterItem>,
 }
@@ -371,11 +496,16 @@ async fn raw_lookup(
     connection: &impl DatastoreConnection,
     keys: Vec<Key>,
 ) -> Result<LookupResponse, DatastorersError> {
+    let cache = readable_cache(connection);
+    if let Some(resp) = cache.and_then(|cache| cache.lookup(&keys)) {
+        return Ok(resp);
+    }
+
     let client = connection.get_client();
     let projects = client.projects();
 
     let req = LookupRequest {
-        keys: Some(keys),
+        keys: Some(keys.clone()),
         read_options: Some(ReadOptions {
             transaction: connection.get_transaction_id(),
             read_consistency: None,
@@ -385,6 +515,9 @@ async fn raw_lookup(
         .lookup(req, connection.get_project_name())
         .execute()
         .await?;
+    if let Some(cache) = cache {
+        cache.insert_lookup(keys, &resp);
+    }
     Ok(resp)
 }
 
@@ -393,9 +526,6 @@ async fn query_one(
     filter: Option<Filter>,
     kind: String,
 ) -> Result<DatastoreEntity, DatastorersError> {
-    let client = connection.get_client();
-    let projects = client.projects();
-
     let query = Query {
         kind: Some(vec![KindExpression { name: Some(kind) }]),
         filter,
@@ -411,10 +541,7 @@ async fn query_one(
         ..Default::default()
     };
 
-    let resp: RunQueryResponse = projects
-        .run_query(req, connection.get_project_name())
-        .execute()
-        .await?;
+    let resp = run_query(connection, req).await?;
 
     match resp.batch {
         Some(batch) => {
@@ -446,18 +573,83 @@ async fn get_page(
     connection: &impl DatastoreConnection,
     query: Query,
 ) -> Result<DatastoreEntityCollection, DatastorersError> {
-    let client = connection.get_client();
-    let projects = client.projects();
     let req = RunQueryRequest {
         query: Some(query.clone()),
         ..Default::default()
     };
+    let resp = run_query(connection, req).await?;
+
+    page_from_batch(resp.batch, query)
+}
+
+pub(crate) async fn run_query(
+    connection: &impl DatastoreConnection,
+    req: RunQueryRequest,
+) -> Result<RunQueryResponse, DatastorersError> {
+    let cache = readable_cache(connection);
+    if let Some(resp) = cache.and_then(|cache| cache.query(&req)) {
+        return Ok(resp);
+    }
+
+    let client = connection.get_client();
+    let projects = client.projects();
     let resp: RunQueryResponse = projects
-        .run_query(req, connection.get_project_name())
+        .run_query(req.clone(), connection.get_project_name())
         .execute()
         .await?;
+    if let Some(cache) = cache {
+        cache.insert_query(&req, &resp);
+    }
+    Ok(resp)
+}
 
-    match resp.batch {
+/// Iterates over all result batches of a query, following the end cursor of each
+/// batch until there are no more results.
+pub(crate) struct QueryBatches {
+    query: Query,
+    done: bool,
+}
+
+impl QueryBatches {
+    pub(crate) fn new(query: Query) -> QueryBatches {
+        QueryBatches { query, done: false }
+    }
+
+    pub(crate) async fn next_batch(
+        &mut self,
+        connection: &impl DatastoreConnection,
+    ) -> Result<Option<Vec<EntityResult>>, DatastorersError> {
+        if self.done {
+            return Ok(None);
+        }
+        let req = RunQueryRequest {
+            query: Some(self.query.clone()),
+            read_options: Some(ReadOptions {
+                transaction: connection.get_transaction_id(),
+                read_consistency: None,
+            }),
+            ..Default::default()
+        };
+        let resp = run_query(connection, req).await?;
+        let batch = resp.batch.ok_or(DatastoreClientError::ApiDataError)?;
+        let more_results = batch
+            .more_results
+            .ok_or(DatastoreClientError::ApiDataError)?;
+        if more_results == QueryResultBatchMoreResults::NoMoreResults {
+            self.done = true;
+        } else {
+            let end_cursor = batch.end_cursor.ok_or(DatastoreClientError::ApiDataError)?;
+            self.query.start_cursor = Some(end_cursor);
+        }
+        Ok(Some(batch.entity_results.unwrap_or_default()))
+    }
+}
+
+pub(crate) fn page_from_batch(
+    batch: Option<QueryResultBatch>,
+    query: Query,
+) -> Result<DatastoreEntityCollection, DatastorersError> {
+    match batch {
         Some(batch) => {
             let more_results = batch
                 .more_results
@@ -507,4 +699,122 @@ where
         let res: ResultCollection<T> = page.try_into()?;
         return Ok(res);
     }
+
+    /// Fetch the page before this page, the results are returned in the order of the query.
+    ///
+    /// The query is run with all order directions inverted, including the key order that
+    /// breaks ties, starting from the start cursor of this page.
+    /// The first page has no start cursor, and has no previous page.
+    pub async fn get_previous_page(
+        self,
+        connection: &impl DatastoreConnection,
+    ) -> Result<ResultCollection<T>, DatastorersError> {
+        let reversed_query = self.previous_page_query()?;
+        let query = self.query.ok_or(DatastoreClientError::NoMorePages)?;
+        let start_cursor = reversed_query
+            .start_cursor
+            .clone()
+            .ok_or(DatastoreClientError::NoMorePages)?;
+        let limit = reversed_query.limit;
+        let req = RunQueryRequest {
+            query: Some(reversed_query),
+            ..Default::default()
+        };
+        let resp = run_query(connection, req).await?;
+
+        let batch = resp.batch.ok_or(DatastoreClientError::ApiDataError)?;
+        let more_results = batch
+            .more_results
+            .ok_or(DatastoreClientError::ApiDataError)?;
+        let entity_results = batch.entity_results.unwrap_or_default();
+        // The reversed query reached the start of the results if it returned fewer results
+        // than the limit, even when datastore reports more results after the limit
+        let fewer_than_limit = match limit {
+            Some(limit) => (entity_results.len() as i64) < i64::from(limit),
+            None => false,
+        };
+        let reached_start =
+            more_results == QueryResultBatchMoreResults::NoMoreResults || fewer_than_limit;
+        // The end of the reversed query is the start of the previous page,
+        // unless the previous page is the first page
+        let previous_start_cursor = if reached_start {
+            None
+        } else {
+            Some(batch.end_cursor.ok_or(DatastoreClientError::ApiDataError)?)
+        };
+        let entities = entity_results
+            .into_iter()
+            .rev()
+            .map(|e| {
+                let result: DatastoreEntity = e.try_into()?;
+                Ok(result)
+            })
+            .collect::<Result<Vec<DatastoreEntity>, DatastorersError>>()?;
+
+        // This page follows the previous page
+        let page = DatastoreEntityCollection::from_result(
+            entities,
+            Query {
+                start_cursor: previous_start_cursor,
+                ..query
+            },
+            start_cursor,
+            true,
+        );
+        page.try_into()
+    }
+}
+
+impl<T> ResultCollection<T> {
+    /// The query run by [get_previous_page](ResultCollection::get_previous_page), the query of
+    /// this page with all order directions inverted, starting from the start cursor of this page.
+    /// [NoMorePages](DatastoreClientError::NoMorePages) is returned for the first page,
+    /// and for results without a query.
+    pub fn previous_page_query(&self) -> Result<Query, DatastorersError> {
+        let query = self
+            .query
+            .as_ref()
+            .ok_or(DatastoreClientError::NoMorePages)?;
+        let start_cursor = query
+            .start_cursor
+            .clone()
+            .ok_or(DatastoreClientError::NoMorePages)?;
+        Ok(Query {
+            order: Some(inverted_order(query.order.clone())),
+            start_cursor: Some(start_cursor),
+            end_cursor: None,
+            offset: None,
+            ..query.clone()
+        })
+    }
+}
+
+/// Invert the direction of all orders. Results are ordered by key after the given orders,
+/// so the key order is inverted as well, and added if it is not the last order.
+fn inverted_order(order: Option<Vec<PropertyOrder>>) -> Vec<PropertyOrder> {
+    let mut inverted: Vec<PropertyOrder> = order
+        .unwrap_or_default()
+        .into_iter()
+        .map(|property_order| PropertyOrder {
+            direction: Some(match property_order.direction {
+                Some(PropertyOrderDirection::Descending) => PropertyOrderDirection::Ascending,
+                _ => PropertyOrderDirection::Descending,
+            }),
+            ..property_order
+        })
+        .collect();
+    let ordered_by_key = inverted
+        .last()
+        .and_then(|last| last.property.as_ref())
+        .and_then(|property| property.name.as_deref())
+        == Some(KEY_PROPERTY);
+    if !ordered_by_key {
+        inverted.push(PropertyOrder {
+            property: Some(PropertyReference {
+                name: Some(String::from(KEY_PROPERTY)),
+            }),
+            direction: Some(PropertyOrderDirection::Descending),
+        });
+    }
+    inverted
 }
@@ -0,0 +1,304 @@
+use google_datastore1::schemas::{
+    Filter, Key, PropertyFilter, PropertyFilterOp, PropertyOrder, PropertyOrderDirection,
+    PropertyReference, Query, Value,
+};
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Result};
+
+const REDACTED: &str = "?";
+
+/// Renders a [Query](Query) as a GQL-like string, for logs and debugging.
+///
+/// Values in filters are rendered as GQL literals, or as `?` when values are
+/// [redacted](QueryDisplay::redact_values). Cursors are rendered with `START CURSOR`
+/// and `END CURSOR`, which is not part of GQL.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind = "Test"]
+/// # #[page_size = 10]
+/// # struct TestEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// #     #[property = "Name"]
+/// #     name: String,
+/// # }
+/// let query = TestEntity::query()
+///     .filter(TestEntity::NAME, Operator::Equal, "test-name".to_string())?
+///     .order_by(TestEntity::NAME, Order::Descending);
+/// assert_eq!(
+///     "SELECT * FROM Test WHERE Name = 'test-name' ORDER BY Name DESC LIMIT 10",
+///     query.display().to_string()
+/// );
+/// assert_eq!(
+///     "SELECT * FROM Test WHERE Name = ? ORDER BY Name DESC LIMIT 10",
+///     query.display().redact_values().to_string()
+/// );
+/// # Ok::<(), DatastorersError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryDisplay<'a> {
+    query: Cow<'a, Query>,
+    redact: bool,
+}
+
+impl<'a> QueryDisplay<'a> {
+    pub fn new(query: &'a Query) -> Self {
+        QueryDisplay {
+            query: Cow::Borrowed(query),
+            redact: false,
+        }
+    }
+
+    /// Render filter values and cursors as `?`, e.g. when they contain personal data
+    pub fn redact_values(mut self) -> Self {
+        self.redact = true;
+        self
+    }
+}
+
+impl From<Query> for QueryDisplay<'static> {
+    fn from(query: Query) -> Self {
+        QueryDisplay {
+            query: Cow::Owned(query),
+            redact: false,
+        }
+    }
+}
+
+impl Display for QueryDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let query = self.query.as_ref();
+
+        write!(f, "SELECT ")?;
+        if let Some(distinct_on) = &query.distinct_on {
+            write!(f, "DISTINCT ON (")?;
+            write_property_list(f, distinct_on)?;
+            write!(f, ") ")?;
+        }
+        match &query.projection {
+            Some(projection) if !projection.is_empty() => {
+                let properties: Vec<PropertyReference> = projection
+                    .iter()
+                    .filter_map(|p| p.property.clone())
+                    .collect();
+                write_property_list(f, &properties)?;
+            }
+            _ => write!(f, "*")?,
+        }
+
+        if let Some(kind) = query
+            .kind
+            .as_ref()
+            .and_then(|kinds| kinds.first())
+            .and_then(|kind| kind.name.as_ref())
+        {
+            write!(f, " FROM {}", Name(kind))?;
+        }
+
+        let mut property_filters = vec![];
+        collect_property_filters(query.filter.as_ref(), &mut property_filters);
+        for (i, property_filter) in property_filters.iter().enumerate() {
+            write!(f, " {} ", if i == 0 { "WHERE" } else { "AND" })?;
+            self.write_property_filter(f, property_filter)?;
+        }
+
+        if let Some(order) = &query.order {
+            for (i, property_order) in order.iter().enumerate() {
+                write!(f, "{}", if i == 0 { " ORDER BY " } else { ", " })?;
+                write_property_order(f, property_order)?;
+            }
+        }
+
+        if let Some(limit) = query.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if let Some(offset) = query.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+        if let Some(start_cursor) = &query.start_cursor {
+            write!(f, " START CURSOR ")?;
+            self.write_redactable(f, |f| write_string(f, start_cursor))?;
+        }
+        if let Some(end_cursor) = &query.end_cursor {
+            write!(f, " END CURSOR ")?;
+            self.write_redactable(f, |f| write_string(f, end_cursor))?;
+        }
+        Ok(())
+    }
+}
+
+impl QueryDisplay<'_> {
+    fn write_property_filter(&self, f: &mut Formatter<'_>, filter: &PropertyFilter) -> Result {
+        let name = filter
+            .property
+            .as_ref()
+            .and_then(|property| property.name.as_deref())
+            .unwrap_or_default();
+        let op = match filter.op {
+            Some(PropertyFilterOp::Equal) => "=",
+            Some(PropertyFilterOp::LessThan) => "<",
+            Some(PropertyFilterOp::LessThanOrEqual) => "<=",
+            Some(PropertyFilterOp::GreaterThan) => ">",
+            Some(PropertyFilterOp::GreaterThanOrEqual) => ">=",
+            Some(PropertyFilterOp::HasAncestor) => "HAS ANCESTOR",
+            _ => "?",
+        };
+        write!(f, "{} {} ", Name(name), op)?;
+        match &filter.value {
+            Some(value) => self.write_redactable(f, |f| write_value(f, value)),
+            None => write!(f, "NULL"),
+        }
+    }
+
+    fn write_redactable(
+        &self,
+        f: &mut Formatter<'_>,
+        write: impl FnOnce(&mut Formatter<'_>) -> Result,
+    ) -> Result {
+        match self.redact {
+            true => write!(f, "{}", REDACTED),
+            false => write(f),
+        }
+    }
+}
+
+/// Renders a [Key](Key) as a GQL key literal, `KEY(Kind, 1, Child, 'name')`
+pub(crate) struct KeyDisplay<'a>(pub &'a Key);
+
+impl Display for KeyDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_key(f, self.0)
+    }
+}
+
+/// A kind or property name, back quoted unless it is a plain identifier
+struct Name<'a>(&'a str);
+
+impl Display for Name<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let plain = !self.0.is_empty()
+            && !self.0.starts_with(|c: char| c.is_ascii_digit())
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+        match plain {
+            true => write!(f, "{}", self.0),
+            false => write!(f, "`{}`", self.0.replace('`', "``")),
+        }
+    }
+}
+
+fn collect_property_filters<'a>(filter: Option<&'a Filter>, into: &mut Vec<&'a PropertyFilter>) {
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return,
+    };
+    if let Some(property_filter) = &filter.property_filter {
+        into.push(property_filter);
+    }
+    if let Some(composite_filter) = &filter.composite_filter {
+        for filter in composite_filter.filters.iter().flatten() {
+            collect_property_filters(Some(filter), into);
+        }
+    }
+}
+
+fn write_property_list(f: &mut Formatter<'_>, properties: &[PropertyReference]) -> Result {
+    for (i, property) in properties.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", Name(property.name.as_deref().unwrap_or_default()))?;
+    }
+    Ok(())
+}
+
+fn write_property_order(f: &mut Formatter<'_>, order: &PropertyOrder) -> Result {
+    let name = order
+        .property
+        .as_ref()
+        .and_then(|property| property.name.as_deref())
+        .unwrap_or_default();
+    let direction = match order.direction {
+        Some(PropertyOrderDirection::Descending) => "DESC",
+        _ => "ASC",
+    };
+    write!(f, "{} {}", Name(name), direction)
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> Result {
+    write!(f, "'{}'", value.replace('\'', "''"))
+}
+
+fn write_key(f: &mut Formatter<'_>, key: &Key) -> Result {
+    write!(f, "KEY(")?;
+    for (i, element) in key.path.iter().flatten().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", Name(element.kind.as_deref().unwrap_or_default()))?;
+        match (element.id, &element.name) {
+            (Some(id), _) => write!(f, ", {}", id)?,
+            (None, Some(name)) => {
+                write!(f, ", ")?;
+                write_string(f, name)?;
+            }
+            (None, None) => (),
+        }
+    }
+    write!(f, ")")
+}
+
+fn write_value(f: &mut Formatter<'_>, value: &Value) -> Result {
+    if let Some(string_value) = &value.string_value {
+        write_string(f, string_value)
+    } else if let Some(integer_value) = value.integer_value {
+        write!(f, "{}", integer_value)
+    } else if let Some(double_value) = value.double_value {
+        write!(f, "{:?}", double_value)
+    } else if let Some(boolean_value) = value.boolean_value {
+        write!(f, "{}", if boolean_value { "TRUE" } else { "FALSE" })
+    } else if let Some(timestamp_value) = &value.timestamp_value {
+        write!(f, "DATETIME(")?;
+        write_string(f, timestamp_value)?;
+        write!(f, ")")
+    } else if let Some(key_value) = &value.key_value {
+        write_key(f, key_value)
+    } else if let Some(blob_value) = &value.blob_value {
+        write!(f, "BLOB(")?;
+        write_string(f, blob_value)?;
+        write!(f, ")")
+    } else if let Some(geo_point_value) = &value.geo_point_value {
+        write!(
+            f,
+            "GEOPOINT({:?}, {:?})",
+            geo_point_value.latitude.unwrap_or_default(),
+            geo_point_value.longitude.unwrap_or_default()
+        )
+    } else if let Some(array_value) = &value.array_value {
+        write!(f, "ARRAY(")?;
+        for (i, value) in array_value.values.iter().flatten().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write_value(f, value)?;
+        }
+        write!(f, ")")
+    } else if let Some(entity_value) = &value.entity_value {
+        write!(f, "ENTITY(")?;
+        for (i, (name, value)) in entity_value.properties.iter().flatten().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} = ", Name(name))?;
+            write_value(f, value)?;
+        }
+        write!(f, ")")
+    } else {
+        write!(f, "NULL")
+    }
+}
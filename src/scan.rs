@@ -0,0 +1,257 @@
+use std::cmp::Ordering;
+use std::convert::{TryFrom, TryInto};
+
+use futures::stream::{self, Stream, TryStreamExt};
+
+use crate::connection::DatastoreConnection;
+use crate::entity::{DatastoreEntity, Keyed, Kind, Pagable};
+use crate::error::{DatastoreClientError, DatastorersError};
+use crate::identifier::KeyPath;
+use crate::query::{run_query, DatastorersQuery, Operator, QueryBatches, KEY_PROPERTY};
+
+use google_datastore1::schemas::{
+    EntityResult, Key, KindExpression, PathElement, Projection, PropertyOrder,
+    PropertyOrderDirection, PropertyReference, Query, ReadOptions, RunQueryRequest,
+};
+
+const SCATTER_PROPERTY: &str = "__scatter__";
+// Number of scatter samples per shard, more samples give more even shards
+const SCATTER_OVERSAMPLING: usize = 32;
+
+/// A query split into shards by key ranges, that are scanned concurrently.
+///
+/// The query must not have inequality filters on other properties than the key,
+/// or sort orders other than [order_by_key](DatastorersQuery::order_by_key), since each shard
+/// adds inequality filters on the key. Streams of a query sorted by other properties fail with
+/// [ScanOrderNotSupported](DatastoreClientError::ScanOrderNotSupported).
+/// Each shard is paged like [fetch](DatastorersQuery::fetch), using the limit of the query as page size.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # use futures::TryStreamExt;
+/// # #[derive(DatastoreManaged)]
+/// # #[kind = "Test"]
+/// # struct TestEntity {
+/// #     #[key]
+/// #     key: IdentifierId<Self>,
+/// # }
+/// async fn export_all(
+///     connection: &impl DatastoreConnection,
+/// ) -> Result<Vec<TestEntity>, DatastorersError> {
+///     TestEntity::query()
+///         .split_by_scatter(connection, 8)
+///         .await?
+///         .into_stream(connection)?
+///         .try_collect()
+///         .await
+/// }
+/// ```
+pub struct ParallelScan<E>
+where
+    E: Kind + Keyed + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    query: DatastorersQuery<E>,
+    // Sorted keys where one shard ends and the next begins
+    split_points: Vec<Key>,
+}
+
+impl<E> DatastorersQuery<E>
+where
+    E: Kind + Keyed + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    /// Split the query into shards at the given keys, `n` split points give `n + 1` shards
+    pub fn split_at(self, split_points: &[E::Key]) -> ParallelScan<E> {
+        let split_points = split_points.iter().map(KeyPath::get_key).collect();
+        ParallelScan::new(self, split_points)
+    }
+
+    /// Split the query into about `shards` shards of similar size, using split points
+    /// sampled from the `__scatter__` property of the kind.
+    /// Fewer shards are created if the kind has few entities.
+    pub async fn split_by_scatter(
+        self,
+        connection: &impl DatastoreConnection,
+        shards: usize,
+    ) -> Result<ParallelScan<E>, DatastorersError> {
+        if shards <= 1 {
+            return Ok(ParallelScan::new(self, vec![]));
+        }
+        let samples = scatter_samples(
+            connection,
+            E::kind_str(),
+            (shards - 1).saturating_mul(SCATTER_OVERSAMPLING),
+        )
+        .await?;
+        Ok(ParallelScan::new(
+            self,
+            scatter_split_points(samples, shards),
+        ))
+    }
+}
+
+impl<E> ParallelScan<E>
+where
+    E: Kind + Keyed + Pagable + TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    fn new(query: DatastorersQuery<E>, mut split_points: Vec<Key>) -> Self {
+        split_points.sort_by(compare_keys);
+        split_points.dedup_by(|a, b| compare_keys(a, b) == Ordering::Equal);
+        ParallelScan {
+            query,
+            split_points,
+        }
+    }
+
+    /// The queries of the shards, in key order
+    pub fn shards(&self) -> Vec<DatastorersQuery<E>> {
+        let mut starts = vec![None];
+        starts.extend(self.split_points.iter().cloned().map(Some));
+        let mut ends: Vec<Option<Key>> = self.split_points.iter().cloned().map(Some).collect();
+        ends.push(None);
+
+        starts
+            .into_iter()
+            .zip(ends)
+            .map(|(start, end)| {
+                let mut query = self.query.clone();
+                if let Some(start) = start {
+                    query = query.key_filter(Operator::GreaterThanOrEqual, start);
+                }
+                if let Some(end) = end {
+                    query = query.key_filter(Operator::LessThan, end);
+                }
+                query
+            })
+            .collect()
+    }
+
+    /// One stream of entities per shard, in key order. The streams run independently of each other.
+    pub fn into_shard_streams<'a>(
+        self,
+        connection: &'a impl DatastoreConnection,
+    ) -> Result<Vec<impl Stream<Item = Result<E, DatastorersError>> + 'a>, DatastorersError>
+    where
+        E: 'a,
+    {
+        if self.query.has_property_order() {
+            return Err(DatastoreClientError::ScanOrderNotSupported.into());
+        }
+        self.shards()
+            .into_iter()
+            .map(|shard| Ok(shard_stream(connection, shard.try_into()?)))
+            .collect()
+    }
+
+    /// The entities of all shards merged in one stream, the shards are scanned concurrently
+    /// and entities are returned in the order they arrive.
+    pub fn into_stream<'a>(
+        self,
+        connection: &'a impl DatastoreConnection,
+    ) -> Result<impl Stream<Item = Result<E, DatastorersError>> + 'a, DatastorersError>
+    where
+        E: 'a,
+    {
+        let shard_streams = self.into_shard_streams(connection)?;
+        Ok(stream::select_all(shard_streams.into_iter().map(Box::pin)))
+    }
+}
+
+fn shard_stream<'a, E>(
+    connection: &'a impl DatastoreConnection,
+    query: Query,
+) -> impl Stream<Item = Result<E, DatastorersError>> + 'a
+where
+    E: TryFrom<DatastoreEntity, Error = DatastorersError> + 'a,
+{
+    stream::try_unfold(QueryBatches::new(query), move |mut batches| async move {
+        let batch = batches.next_batch(connection).await?;
+        Ok::<_, DatastorersError>(batch.map(|batch| (batch, batches)))
+    })
+    .map_ok(|batch| stream::iter(batch.into_iter().map(entity_from_result)))
+    .try_flatten()
+}
+
+fn entity_from_result<E>(result: EntityResult) -> Result<E, DatastorersError>
+where
+    E: TryFrom<DatastoreEntity, Error = DatastorersError>,
+{
+    let entity: DatastoreEntity = result.try_into()?;
+    entity.try_into()
+}
+
+/// Keys of the kind in `__scatter__` order, which is a random sample of the keys
+async fn scatter_samples(
+    connection: &impl DatastoreConnection,
+    kind: &str,
+    samples: usize,
+) -> Result<Vec<Key>, DatastorersError> {
+    let query = Query {
+        kind: Some(vec![KindExpression {
+            name: Some(kind.to_string()),
+        }]),
+        projection: Some(vec![Projection {
+            property: Some(PropertyReference {
+                name: Some(String::from(KEY_PROPERTY)),
+            }),
+        }]),
+        order: Some(vec![PropertyOrder {
+            property: Some(PropertyReference {
+                name: Some(String::from(SCATTER_PROPERTY)),
+            }),
+            direction: Some(PropertyOrderDirection::Ascending),
+        }]),
+        limit: Some(i32::try_from(samples).unwrap_or(i32::MAX)),
+        ..Default::default()
+    };
+    let req = RunQueryRequest {
+        query: Some(query),
+        read_options: Some(ReadOptions {
+            transaction: connection.get_transaction_id(),
+            read_consistency: None,
+        }),
+        ..Default::default()
+    };
+    let resp = run_query(connection, req).await?;
+    let batch = resp.batch.ok_or(DatastoreClientError::ApiDataError)?;
+    Ok(batch
+        .entity_results
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|result| result.entity.and_then(|entity| entity.key))
+        .collect())
+}
+
+/// Pick `shards - 1` evenly spaced split points from the sampled keys
+fn scatter_split_points(mut samples: Vec<Key>, shards: usize) -> Vec<Key> {
+    samples.sort_by(compare_keys);
+    if samples.len() < shards {
+        return samples;
+    }
+    (1..shards)
+        .map(|shard| samples[shard * samples.len() / shards].clone())
+        .collect()
+}
+
+/// Compare keys in the order used by datastore, element by element
+/// where ids are ordered before names, and a key is ordered before its descendants.
+pub(crate) fn compare_keys(a: &Key, b: &Key) -> Ordering {
+    let a_path = a.path.as_deref().unwrap_or_default();
+    let b_path = b.path.as_deref().unwrap_or_default();
+    for (a_element, b_element) in a_path.iter().zip(b_path) {
+        let ordering = compare_path_elements(a_element, b_element);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_path.len().cmp(&b_path.len())
+}
+
+fn compare_path_elements(a: &PathElement, b: &PathElement) -> Ordering {
+    a.kind.cmp(&b.kind).then_with(|| match (a.id, b.id) {
+        (Some(a_id), Some(b_id)) => a_id.cmp(&b_id),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.name.cmp(&b.name),
+    })
+}
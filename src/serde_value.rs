@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use google_datastore1::schemas::{ArrayValue, Entity, ValueNullValue};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value as JsonValue};
+
+use crate::deserialize::{DatastoreDeserializeError, Deserialize};
+use crate::entity::DatastoreValue;
+use crate::serialize::{DatastoreSerializeError, Serialize};
+
+/// Stores a type implementing serde `Serialize` and `Deserialize` as a native datastore value.
+///
+/// Structs and maps are stored as embedded entities, sequences as arrays and `None` as null.
+/// Integers must fit in an `i64`, and datastore does not support arrays directly
+/// within arrays, use [SerdeJson] for such types.
+///
+/// Example:
+/// ```
+/// # use datastorers::*;
+/// # use datastorers::serde_value::SerdeValue;
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+/// struct Preferences {
+///     theme: String,
+///     notifications: bool,
+/// }
+///
+/// #[derive(DatastoreManaged, Clone, Debug)]
+/// #[kind = "user"]
+/// struct User {
+///     #[key]
+///     key: IdentifierId<Self>,
+///     preferences: SerdeValue<Preferences>,
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SerdeValue<T>(pub T);
+
+/// Stores a type implementing serde `Serialize` and `Deserialize` as a JSON string.
+/// The string is excluded from indexes, so the property can not be filtered on.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SerdeJson<T>(pub T);
+
+impl<T: serde::Serialize> Serialize for SerdeValue<T> {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        let json = serde_json::to_value(&self.0)
+            .map_err(|err| DatastoreSerializeError::SerdeError(err.to_string()))?;
+        Ok(Some(from_json(json, false)?))
+    }
+}
+
+impl<T: DeserializeOwned + Debug> Deserialize for SerdeValue<T> {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        serde_json::from_value(into_json(value)?)
+            .map(SerdeValue)
+            .map_err(|err| DatastoreDeserializeError::SerdeError(err.to_string()))
+    }
+}
+
+impl<T: serde::Serialize> Serialize for SerdeJson<T> {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        let json = serde_json::to_string(&self.0)
+            .map_err(|err| DatastoreSerializeError::SerdeError(err.to_string()))?;
+        let mut value = DatastoreValue::empty();
+        value.string_value = Some(json);
+        value.exclude_from_indexes = Some(true);
+        Ok(Some(value))
+    }
+}
+
+impl<T: DeserializeOwned + Debug> Deserialize for SerdeJson<T> {
+    fn deserialize(value: DatastoreValue) -> Result<Self, DatastoreDeserializeError> {
+        let json = value
+            .0
+            .string_value
+            .ok_or(DatastoreDeserializeError::NoSuchValue)?;
+        serde_json::from_str(&json)
+            .map(SerdeJson)
+            .map_err(|err| DatastoreDeserializeError::SerdeError(err.to_string()))
+    }
+}
+
+fn from_json(json: JsonValue, in_array: bool) -> Result<DatastoreValue, DatastoreSerializeError> {
+    let mut value = DatastoreValue::empty();
+    match json {
+        JsonValue::Null => value.null_value = Some(ValueNullValue::NullValue),
+        JsonValue::Bool(boolean) => value.boolean_value = Some(boolean),
+        JsonValue::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(integer), _) => value.integer_value = Some(integer),
+            (None, Some(integer)) => {
+                return Err(DatastoreSerializeError::IntegerOutOfRange { value: integer })
+            }
+            (None, None) => value.double_value = number.as_f64(),
+        },
+        JsonValue::String(string) => value.string_value = Some(string),
+        JsonValue::Array(_) if in_array => {
+            return Err(DatastoreSerializeError::SerdeError(String::from(
+                "arrays can not contain arrays",
+            )))
+        }
+        JsonValue::Array(array) => {
+            let values = array
+                .into_iter()
+                .map(|json| from_json(json, true).map(|value| value.0))
+                .collect::<Result<Vec<_>, _>>()?;
+            value.array_value = Some(ArrayValue {
+                values: Some(values),
+            });
+        }
+        JsonValue::Object(object) => {
+            let properties = object
+                .into_iter()
+                .map(|(name, json)| from_json(json, false).map(|value| (name, value.0)))
+                .collect::<Result<BTreeMap<_, _>, _>>()?;
+            value.entity_value = Some(Entity {
+                key: None,
+                properties: Some(properties),
+            });
+        }
+    }
+    Ok(value)
+}
+
+fn into_json(value: DatastoreValue) -> Result<JsonValue, DatastoreDeserializeError> {
+    let value = value.0;
+    if let Some(boolean) = value.boolean_value {
+        Ok(JsonValue::Bool(boolean))
+    } else if let Some(integer) = value.integer_value {
+        Ok(JsonValue::Number(integer.into()))
+    } else if let Some(double) = value.double_value {
+        Ok(Number::from_f64(double).map_or(JsonValue::Null, JsonValue::Number))
+    } else if let Some(string) = value
+        .string_value
+        .or(value.timestamp_value)
+        .or(value.blob_value)
+    {
+        Ok(JsonValue::String(string))
+    } else if let Some(array) = value.array_value {
+        array
+            .values
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| into_json(DatastoreValue(value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(JsonValue::Array)
+    } else if let Some(entity) = value.entity_value {
+        entity
+            .properties
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| Ok((name, into_json(DatastoreValue(value))?)))
+            .collect::<Result<Map<_, _>, _>>()
+            .map(JsonValue::Object)
+    } else if value.key_value.is_some() || value.geo_point_value.is_some() {
+        Err(DatastoreDeserializeError::SerdeError(String::from(
+            "key and geo point values can not be read as serde values",
+        )))
+    } else {
+        Ok(JsonValue::Null)
+    }
+}
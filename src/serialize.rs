@@ -1,9 +1,13 @@
 use crate::bytes::Bytes;
-use crate::DatastoreValue;
-use chrono::naive::NaiveDateTime;
-use chrono::{DateTime, LocalResult, SecondsFormat, TimeZone, Utc};
+use crate::error::DatastoreGeoError;
+use crate::{DatastoreProperties, DatastoreValue};
+use chrono::naive::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, LocalResult, SecondsFormat, SubsecRound, TimeZone, Utc};
 use google_datastore1::schemas::ArrayValue;
 use radix64::STD as BASE64_CFG;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::time::SystemTime;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -12,6 +16,15 @@ pub enum DatastoreSerializeError {
     DateTimeError,
     #[error("A non empty value must be set")]
     NoValueError,
+    #[error("Integer {value} is out of range for integer_value (i64)")]
+    IntegerOutOfRange { value: u64 },
+    #[error(transparent)]
+    GeoError(#[from] DatastoreGeoError),
+    #[error("Only complete keys can be stored as key_value")]
+    IncompleteKey,
+    #[cfg(feature = "serde")]
+    #[error("Could not serialize serde value: {0}")]
+    SerdeError(String),
 }
 
 pub trait Serialize {
@@ -44,6 +57,25 @@ impl<T: Serialize> Serialize for Vec<T> {
     }
 }
 
+/// Blanket impl for BTreeMap, stored as an embedded entity with the keys as property names.
+/// Entries with values that serialize to nothing, like `None`, are left out.
+impl<T: Serialize> Serialize for BTreeMap<String, T> {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        let mut properties = DatastoreProperties::new();
+        for (key, value) in self {
+            properties.set_value(&key, value)?;
+        }
+        properties.serialize()
+    }
+}
+
+/// Blanket impl for HashMap, stored like BTreeMap
+impl<T: Serialize, S> Serialize for HashMap<String, T, S> {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        self.into_iter().collect::<BTreeMap<_, _>>().serialize()
+    }
+}
+
 impl Serialize for String {
     fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
         let mut value = DatastoreValue::empty();
@@ -60,6 +92,48 @@ impl Serialize for i64 {
     }
 }
 
+/// Integer types that always fit in an i64
+macro_rules! serialize_integer {
+    ($($int:ty),*) => {
+        $(
+            impl Serialize for $int {
+                fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+                    i64::from(self).serialize()
+                }
+            }
+        )*
+    };
+}
+
+serialize_integer!(i8, i16, i32, u8, u16, u32);
+
+/// Integer types that may be too large for an i64
+macro_rules! serialize_unsigned_integer {
+    ($($int:ty),*) => {
+        $(
+            impl Serialize for $int {
+                fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+                    match i64::try_from(self) {
+                        Ok(value) => value.serialize(),
+                        Err(_) => Err(DatastoreSerializeError::IntegerOutOfRange {
+                            value: self as u64,
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+serialize_unsigned_integer!(u64, usize);
+
+impl Serialize for isize {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        // isize is at most 64 bits on all supported platforms
+        (self as i64).serialize()
+    }
+}
+
 impl Serialize for f64 {
     fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
         let mut value = DatastoreValue::empty();
@@ -68,6 +142,12 @@ impl Serialize for f64 {
     }
 }
 
+impl Serialize for f32 {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        f64::from(self).serialize()
+    }
+}
+
 impl Serialize for bool {
     fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
         let mut value = DatastoreValue::empty();
@@ -85,9 +165,32 @@ impl Serialize for Bytes {
     }
 }
 
-impl Serialize for NaiveDateTime {
+// Datastore stores timestamps with microsecond precision, unless millisecond
+// precision is selected globally with the `millisecond_timestamps` feature.
+// Features are unified across the dependency graph, so the feature affects every crate
+// using datastorers in the build, not only the crate that enables it.
+#[cfg(not(feature = "millisecond_timestamps"))]
+const TIMESTAMP_PRECISION: SecondsFormat = SecondsFormat::Micros;
+#[cfg(feature = "millisecond_timestamps")]
+const TIMESTAMP_PRECISION: SecondsFormat = SecondsFormat::Millis;
+
+impl Serialize for DateTime<Utc> {
     fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
         let mut value = DatastoreValue::empty();
+        value.timestamp_value = Some(self.to_rfc3339_opts(TIMESTAMP_PRECISION, true));
+        Ok(Some(value))
+    }
+}
+
+/// Stored in UTC, the offset is not kept
+impl Serialize for DateTime<FixedOffset> {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        self.with_timezone(&Utc).serialize()
+    }
+}
+
+impl Serialize for NaiveDateTime {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
         let date_time: DateTime<Utc> = match Utc.from_local_datetime(&self) {
             LocalResult::None => return Err(DatastoreSerializeError::DateTimeError),
             LocalResult::Single(date_time) => date_time,
@@ -96,8 +199,63 @@ impl Serialize for NaiveDateTime {
                 to
             }
         };
-        // Datastore requires timestamp_value to have at most 3 decimals (millis) for seconds.
-        value.timestamp_value = Some(date_time.to_rfc3339_opts(SecondsFormat::Millis, true));
-        Ok(Some(value))
+        date_time.serialize()
+    }
+}
+
+/// Stored as a timestamp at midnight UTC
+impl Serialize for NaiveDate {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        self.and_hms_opt(0, 0, 0)
+            .ok_or(DatastoreSerializeError::DateTimeError)?
+            .serialize()
+    }
+}
+
+impl Serialize for SystemTime {
+    fn serialize(self) -> Result<Option<DatastoreValue>, DatastoreSerializeError> {
+        DateTime::<Utc>::from(self).serialize()
+    }
+}
+
+/// Truncate timestamps to millisecond precision before they are serialized.
+///
+/// Applied to a field with the `#[truncate_millis]` attribute of the `DatastoreManaged` derive.
+/// The `millisecond_timestamps` feature truncates all timestamps instead, but since cargo
+/// unifies features it then applies to every crate in the build that uses datastorers,
+/// so it should only be enabled by the final application, never by a library.
+pub trait TruncateMillis {
+    fn truncate_millis(self) -> Self;
+}
+
+impl<T: TruncateMillis> TruncateMillis for Option<T> {
+    fn truncate_millis(self) -> Self {
+        self.map(TruncateMillis::truncate_millis)
+    }
+}
+
+impl<T: TruncateMillis> TruncateMillis for Vec<T> {
+    fn truncate_millis(self) -> Self {
+        self.into_iter()
+            .map(TruncateMillis::truncate_millis)
+            .collect()
+    }
+}
+
+impl<Tz: TimeZone> TruncateMillis for DateTime<Tz> {
+    fn truncate_millis(self) -> Self {
+        self.trunc_subsecs(3)
+    }
+}
+
+impl TruncateMillis for NaiveDateTime {
+    fn truncate_millis(self) -> Self {
+        self.trunc_subsecs(3)
+    }
+}
+
+impl TruncateMillis for SystemTime {
+    fn truncate_millis(self) -> Self {
+        DateTime::<Utc>::from(self).truncate_millis().into()
     }
 }
@@ -1,3 +1,4 @@
+use crate::cache::{mutation_keys, DatastoreCache};
 use crate::connection::DatastoreConnection;
 use crate::entity::DatastoreEntity;
 use crate::error::{DatastoreClientError, DatastorersError};
@@ -28,6 +29,10 @@ impl DatastoreConnection for TransactionConnection<'_> {
     fn get_transaction_id(&self) -> Option<String> {
         Some(self.transaction_id.clone())
     }
+
+    fn get_cache(&self) -> Option<&DatastoreCache> {
+        self.connection.get_cache()
+    }
 }
 
 impl TransactionConnection<'_> {
@@ -96,6 +101,8 @@ impl TransactionConnection<'_> {
     }
 
     pub async fn commit(self) -> Result<(), DatastorersError> {
+        let written_keys = mutation_keys(&self.mutations);
+
         let client = self.connection.get_client();
         let projects = client.projects();
         let commit_request = projects.commit(
@@ -109,6 +116,12 @@ impl TransactionConnection<'_> {
 
         let cr: CommitResponse = commit_request.execute().await?;
 
+        if let Some(cache) = self.connection.get_cache() {
+            written_keys
+                .iter()
+                .for_each(|key| cache.invalidate_key(key));
+        }
+
         // Validate result for conflicts
         if let Some(results) = cr.mutation_results {
             for result in results {
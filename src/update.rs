@@ -10,6 +10,8 @@ use google_datastore1::schemas::{
 
 use crate::entity::DatastoreEntity;
 
+use crate::cache::mutation_keys;
+
 use crate::connection::DatastoreConnection;
 use crate::error::{DatastoreClientError, DatastorersError};
 
@@ -57,6 +59,7 @@ async fn commit(
     );
     let begin_transaction: BeginTransactionResponse = builder.execute().await?;
 
+    let written_keys = mutation_keys(&mutations);
     let commit_request = projects.commit(
         CommitRequest {
             mode: None,
@@ -66,7 +69,13 @@ async fn commit(
         connection.get_project_name(),
     );
 
-    commit_request.execute().await
+    let commit_response = commit_request.execute().await?;
+    if let Some(cache) = connection.get_cache() {
+        written_keys
+            .iter()
+            .for_each(|key| cache.invalidate_key(key));
+    }
+    Ok(commit_response)
 }
 
 fn expects_key_after_commit(key: &Option<Key>) -> Result<bool, DatastoreClientError> {
@@ -1,11 +1,21 @@
-use chrono::{NaiveDateTime, Utc};
-use datastorers::deserialize::Deserialize;
-use datastorers::serialize::Serialize;
-use datastorers::{DatastoreEntity, DatastoreManaged, DatastoreValue, DatastorersError};
-use datastorers::{IdentifierId, IdentifierNone, Kind};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use datastorers::bytes::Bytes;
+use datastorers::deserialize::{DatastoreDeserializeError, Deserialize};
+use datastorers::error::DatastoreGeoError;
+use datastorers::geo::GeoPoint;
+use datastorers::index::{CompositeIndex, DatastoreIndexes, IndexYaml};
+use datastorers::serialize::{DatastoreSerializeError, Serialize};
+use datastorers::{
+    DatastoreEmbedded, DatastoreEntity, DatastoreEnum, DatastoreManaged, DatastoreProperties,
+    DatastoreValue, DatastorersError, Property,
+};
+use datastorers::{DatastorersQueryable, IdentifierId, IdentifierNone, Kind, Operator, Order};
 use float_cmp::approx_eq;
-use std::convert::TryInto;
+use google_datastore1::schemas::{Entity, LatLng, Query};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
 use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub enum TestError {
@@ -33,6 +43,16 @@ pub struct Thing {
     pub prop_date: NaiveDateTime,
 }
 
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "thingy"]
+pub struct PartialThing {
+    #[key]
+    pub key_is_good: IdentifierId<Self>,
+    pub prop_string: String,
+    #[extra]
+    pub extra: DatastoreProperties,
+}
+
 #[derive(DatastoreManaged, Clone, Debug)]
 #[kind = "thingy"]
 pub struct VersionedThing {
@@ -45,6 +65,180 @@ pub struct VersionedThing {
     pub prop_string: String,
 }
 
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "thingy"]
+pub struct RenamedThing {
+    #[key]
+    pub key: IdentifierId<Self>,
+    #[property = "Name"]
+    pub name: String,
+    pub tags: Vec<String>,
+    pub score: Option<f64>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "indexed_thingy"]
+#[index(properties(name = "asc", score = "desc"))]
+#[index(ancestor, properties(score = "asc"))]
+pub struct IndexedThing {
+    #[key]
+    pub key: IdentifierId<Self>,
+    #[property = "Name"]
+    pub name: String,
+    pub score: i64,
+}
+
+#[derive(DatastoreEmbedded, Clone, Debug, PartialEq)]
+pub struct Address {
+    #[property = "City"]
+    pub city: String,
+    pub street: Option<String>,
+    pub country: Option<Country>,
+}
+
+#[derive(DatastoreEmbedded, Clone, Debug, PartialEq)]
+pub struct Country {
+    #[property = "Code"]
+    pub code: String,
+}
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "person"]
+pub struct Person {
+    #[key]
+    pub key: IdentifierId<Self>,
+    #[property = "Home"]
+    pub home: Address,
+    pub work: Option<Address>,
+    pub previous: Vec<Address>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "numbers"]
+pub struct Numbers {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub prop_i8: i8,
+    pub prop_i16: i16,
+    pub prop_i32: i32,
+    pub prop_isize: isize,
+    pub prop_u8: u8,
+    pub prop_u16: u16,
+    pub prop_u32: u32,
+    pub prop_u64: u64,
+    pub prop_usize: usize,
+    pub prop_f32: f32,
+    pub prop_optional_u16: Option<u16>,
+    pub prop_u32_array: Vec<u32>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "timestamps"]
+pub struct Timestamps {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub prop_utc: DateTime<Utc>,
+    pub prop_fixed_offset: DateTime<FixedOffset>,
+    pub prop_date: NaiveDate,
+    pub prop_system_time: SystemTime,
+    pub prop_optional_utc: Option<DateTime<Utc>>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "timestamps"]
+pub struct TruncatedTimestamps {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub prop_micros: NaiveDateTime,
+    #[truncate_millis]
+    pub prop_millis: NaiveDateTime,
+    #[truncate_millis]
+    pub prop_millis_array: Vec<DateTime<Utc>>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "places"]
+pub struct Place {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub location: GeoPoint,
+    pub location_hash: String,
+    pub previous_locations: Vec<GeoPoint>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "settings"]
+pub struct Settings {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub labels: HashMap<String, String>,
+    pub limits: BTreeMap<String, i64>,
+    pub addresses: BTreeMap<String, Address>,
+}
+
+#[derive(DatastoreEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    #[property = "open"]
+    Open,
+    #[property = "closed"]
+    Closed,
+}
+
+#[derive(DatastoreEnum, Clone, Copy, Debug, PartialEq)]
+#[stored_as = "integer"]
+pub enum Priority {
+    Low = -1,
+    Normal,
+    High = 10,
+}
+
+#[derive(DatastoreEnum, Clone, Debug, PartialEq)]
+#[tag = "kind"]
+pub enum Assignee {
+    Nobody,
+    #[property = "person"]
+    Person {
+        #[property = "Name"]
+        name: String,
+        email: Option<String>,
+    },
+    Team(i64, String),
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "tasks"]
+pub struct Task {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub status: Status,
+    pub priority: Option<Priority>,
+    pub previous_statuses: Vec<Status>,
+    pub assignees: Vec<Assignee>,
+}
+
+#[derive(DatastoreEmbedded, Clone, Debug, PartialEq)]
+pub struct Revision {
+    pub author: String,
+    #[indexed(false)]
+    pub diff: String,
+}
+
+#[derive(DatastoreManaged, Debug)]
+#[kind = "articles"]
+pub struct Article {
+    #[key]
+    pub key: IdentifierId<Self>,
+    #[indexed]
+    pub title: String,
+    #[unindexed]
+    pub body: String,
+    #[indexed(false)]
+    pub tags: Vec<String>,
+    pub summary: String,
+    pub thumbnail: Bytes,
+    pub revisions: Vec<Revision>,
+}
+
 fn datastore_timestamp_now() -> NaiveDateTime {
     let now = Utc::now().naive_utc();
     // Make `now` into datastore accepted format string wrapped in a DatastoreValue
@@ -118,8 +312,321 @@ fn into_datastore_entity_and_back() -> Result<(), DatastorersError> {
 }
 
 #[test]
+fn extra_properties_are_preserved() -> Result<(), DatastorersError> {
+    let now = datastore_timestamp_now();
+    let thing = Thing {
+        key_is_good: IdentifierId::id(Some(5), IdentifierNone::none()),
+        prop_string: "StrStr".to_string(),
+        prop_integer: 777,
+        prop_double: 987.12,
+        prop_boolean: true,
+        prop_str_array: vec![String::from("Str"), String::from("Array")],
+        prop_date: now,
+    };
+    let entity: DatastoreEntity = thing.try_into()?;
+
+    let mut partial: PartialThing = entity.try_into()?;
+    assert_eq!("StrStr", partial.prop_string);
+    let mut extra = partial.extra.clone().into_map();
+    assert_eq!(5, extra.len());
+    assert!(!extra.contains_key("prop_string"));
+    assert_eq!(
+        Some(777),
+        extra.remove("prop_integer").unwrap().integer_value
+    );
+
+    partial.prop_string = "Changed".to_string();
+    let entity: DatastoreEntity = partial.try_into()?;
+    let thing_is_back: Thing = entity.try_into()?;
+    assert_eq!("Changed", thing_is_back.prop_string);
+    assert_eq!(777, thing_is_back.prop_integer);
+    assert!(thing_is_back.prop_boolean);
+    assert_eq!(
+        vec![String::from("Str"), String::from("Array")],
+        thing_is_back.prop_str_array
+    );
+    assert_eq!(now, thing_is_back.prop_date);
+    Ok(())
+}
+
+#[test]
+fn property_errors_describe_the_property() -> Result<(), DatastorersError> {
+    let mut extra = DatastoreProperties::new();
+    extra.set("prop_integer", "777".to_string())?;
+    let partial = PartialThing {
+        key_is_good: IdentifierId::id(Some(5), IdentifierNone::none()),
+        prop_string: "StrStr".to_string(),
+        extra,
+    };
+    let entity: DatastoreEntity = partial.clone().try_into()?;
+    let error = match Thing::try_from(entity) {
+        Err(DatastorersError::DatastorePropertyError(error)) => error,
+        result => panic!("expected a property error, got {:?}", result),
+    };
+    assert_eq!("thingy", error.kind);
+    assert_eq!("KEY(thingy, 5)", error.key);
+    assert_eq!("prop_integer", error.property);
+    assert_eq!("i64", error.expected);
+    assert_eq!("string", error.found);
+    assert_eq!(
+        DatastoreDeserializeError::WrongValueType { found: "string" },
+        error.source
+    );
+    assert_eq!(
+        "could not read property \"prop_integer\" of thingy entity KEY(thingy, 5), expected i64 found string",
+        error.to_string()
+    );
+
+    let mut partial = partial;
+    partial.extra = DatastoreProperties::new();
+    let entity: DatastoreEntity = partial.clone().try_into()?;
+    match Thing::try_from(entity) {
+        Err(DatastorersError::DatastorePropertyError(error)) => {
+            assert_eq!("prop_integer", error.property);
+            assert_eq!("missing", error.found);
+            assert_eq!(DatastoreDeserializeError::NoSuchValue, error.source);
+        }
+        result => panic!("expected a property error, got {:?}", result),
+    }
+
+    // Generic types are named without module paths
+    partial.extra = DatastoreProperties::new();
+    partial.extra.set("prop_integer", 777)?;
+    partial.extra.set("prop_double", 1.5)?;
+    partial.extra.set("prop_boolean", true)?;
+    partial.extra.set("prop_str_array", 5)?;
+    let entity: DatastoreEntity = partial.try_into()?;
+    match Thing::try_from(entity) {
+        Err(DatastorersError::DatastorePropertyError(error)) => {
+            assert_eq!("prop_str_array", error.property);
+            assert_eq!("Vec<String>", error.expected);
+            assert_eq!("integer", error.found);
+        }
+        result => panic!("expected a property error, got {:?}", result),
+    }
+    Ok(())
+}
+
+#[test]
+fn numeric_types_into_datastore_entity_and_back() -> Result<(), DatastorersError> {
+    let numbers = Numbers {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        prop_i8: i8::MIN,
+        prop_i16: i16::MIN,
+        prop_i32: i32::MIN,
+        prop_isize: -1,
+        prop_u8: u8::MAX,
+        prop_u16: u16::MAX,
+        prop_u32: u32::MAX,
+        prop_u64: i64::MAX as u64,
+        prop_usize: 17,
+        prop_f32: 1.5,
+        prop_optional_u16: Some(7),
+        prop_u32_array: vec![1, 2, u32::MAX],
+    };
+    let entity: DatastoreEntity = numbers.clone().try_into()?;
+    let numbers_are_back: Numbers = entity.try_into()?;
+    assert_eq!(numbers, numbers_are_back);
+    Ok(())
+}
+
+#[test]
+fn integers_out_of_range() {
+    let mut value = DatastoreValue::empty();
+    value.integer_value = Some(256);
+    assert_eq!(
+        Err(DatastoreDeserializeError::IntegerOutOfRange {
+            value: 256,
+            target: "u8"
+        }),
+        u8::deserialize(value.clone())
+    );
+    assert_eq!(Ok(256), u16::deserialize(value.clone()));
+    assert_eq!(Ok(Some(256)), Option::<i16>::deserialize(value));
+
+    let mut value = DatastoreValue::empty();
+    value.integer_value = Some(-1);
+    assert_eq!(
+        Err(DatastoreDeserializeError::IntegerOutOfRange {
+            value: -1,
+            target: "u64"
+        }),
+        u64::deserialize(value)
+    );
+
+    assert_eq!(
+        Err(DatastoreSerializeError::IntegerOutOfRange { value: u64::MAX }),
+        u64::MAX.serialize().map(|_| ())
+    );
+}
+
+#[test]
+fn typed_property_descriptors() -> Result<(), DatastorersError> {
+    assert_eq!("Name", RenamedThing::NAME.name());
+    assert_eq!("tags", RenamedThing::TAGS.name());
+    assert_eq!("score", RenamedThing::SCORE.name());
+
+    let query: Query = RenamedThing::query()
+        .filter(RenamedThing::NAME, Operator::Equal, "thing".to_string())?
+        .filter(RenamedThing::TAGS, Operator::Equal, "tag".to_string())?
+        .filter(RenamedThing::SCORE, Operator::GreaterThan, 0.5)?
+        .order_by(RenamedThing::SCORE, Order::Descending)
+        .try_into()?;
+
+    let filters = query
+        .filter
+        .unwrap()
+        .composite_filter
+        .unwrap()
+        .filters
+        .unwrap();
+    let names: Vec<String> = filters
+        .into_iter()
+        .map(|f| f.property_filter.unwrap().property.unwrap().name.unwrap())
+        .collect();
+    assert_eq!(vec!["Name", "tags", "score"], names);
+    let order = query.order.unwrap();
+    assert_eq!(
+        "score",
+        order[0].property.as_ref().unwrap().name.as_ref().unwrap()
+    );
+    Ok(())
+}
+
+#[test]
+fn nested_property_descriptors() -> Result<(), DatastorersError> {
+    let home_city: Property<Person, String> = Person::HOME.nested(Address::CITY);
+    assert_eq!("Home.City", home_city.name());
+    let previous_street: Property<Person, String> = Person::PREVIOUS.nested(Address::STREET);
+    assert_eq!("previous.street", previous_street.name());
+
+    let query = Person::query()
+        .filter(&home_city, Operator::Equal, "Gothenburg".to_string())?
+        .filter(previous_street, Operator::Equal, "Main street".to_string())?
+        .order_by(home_city, Order::Ascending);
+    assert_eq!(
+        "SELECT * FROM person WHERE `Home.City` = 'Gothenburg' \
+         AND `previous.street` = 'Main street' ORDER BY `Home.City` ASC LIMIT 50",
+        query.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn embedded_into_datastore_entity_and_back() -> Result<(), DatastorersError> {
+    let home = Address {
+        city: "Gothenburg".to_string(),
+        street: Some("Main street".to_string()),
+        country: Some(Country {
+            code: "SE".to_string(),
+        }),
+    };
+    let previous = Address {
+        city: "Stockholm".to_string(),
+        street: None,
+        country: None,
+    };
+    let person = Person {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        home: home.clone(),
+        work: None,
+        previous: vec![previous.clone(), home.clone()],
+    };
+
+    let entity: DatastoreEntity = person.try_into()?;
+    let person_is_back: Person = entity.clone().try_into()?;
+    assert_eq!(home, person_is_back.home);
+    assert_eq!(None, person_is_back.work);
+    assert_eq!(vec![previous, home], person_is_back.previous);
+
+    // Embedded entities use the renamed properties, and have no key
+    let mut properties = Entity::try_from(entity)?.properties.unwrap();
+    let home = properties.remove("Home").unwrap().entity_value.unwrap();
+    assert_eq!(None, home.key);
+    let mut home_properties = home.properties.unwrap();
+    assert_eq!(
+        Some("Gothenburg".to_string()),
+        home_properties.remove("City").unwrap().string_value
+    );
+    let country = home_properties.remove("country").unwrap().entity_value;
+    assert!(country.unwrap().properties.unwrap().contains_key("Code"));
+    assert!(!properties.contains_key("work"));
+    Ok(())
+}
+
+#[test]
+fn embedded_missing_property() {
+    let mut value = DatastoreValue::empty();
+    value.entity_value = Some(Entity {
+        key: None,
+        properties: Some(BTreeMap::new()),
+    });
+    assert_eq!(
+        DatastoreDeserializeError::NoSuchValue,
+        Address::deserialize(value.clone()).unwrap_err()
+    );
+    assert_eq!(
+        Ok(None),
+        Option::<Country>::deserialize(DatastoreValue::empty())
+    );
+}
+
+#[test]
+fn untyped_property_names() -> Result<(), DatastorersError> {
+    let query = Query::try_from(
+        RenamedThing::query()
+            .filter(String::from("Name"), Operator::Equal, 5)?
+            .order_by(String::from("Name"), Order::Ascending),
+    )?;
+    let filter = query.filter.unwrap().property_filter.unwrap();
+    assert_eq!("Name", filter.property.unwrap().name.unwrap());
+    assert_eq!(Some(5), filter.value.unwrap().integer_value);
+    Ok(())
+}
+
+#[test]
+fn declared_indexes() {
+    assert_eq!(
+        vec![
+            CompositeIndex::new(
+                "indexed_thingy",
+                false,
+                vec![("Name", Order::Ascending), ("score", Order::Descending)]
+            ),
+            CompositeIndex::new("indexed_thingy", true, vec![("score", Order::Ascending)]),
+        ],
+        IndexedThing::indexes()
+    );
+    assert!(Thing::indexes().is_empty());
+}
+
+#[test]
+fn merged_index_yaml() {
+    let index_yaml = IndexYaml::new()
+        .entity::<IndexedThing>()
+        .entity::<Thing>()
+        .entity::<IndexedThing>()
+        .to_string();
+    let expected = r#"indexes:
+- kind: "indexed_thingy"
+  properties:
+  - name: "Name"
+    direction: asc
+  - name: "score"
+    direction: desc
+- kind: "indexed_thingy"
+  ancestor: yes
+  properties:
+  - name: "score"
+    direction: asc
+"#;
+    assert_eq!(expected, index_yaml);
+}
+
+#[test]
+#[cfg(not(feature = "millisecond_timestamps"))]
 fn timestap_deserialize_serialize() -> Result<(), Box<dyn Error>> {
-    let date_str = "2021-01-02T12:53:39.392Z";
+    let date_str = "2021-01-02T12:53:39.392871Z";
     let mut value_to_deserialize = DatastoreValue::empty();
     value_to_deserialize.timestamp_value = Some(date_str.to_string());
     let date_time = NaiveDateTime::deserialize(value_to_deserialize)?;
@@ -130,6 +637,374 @@ fn timestap_deserialize_serialize() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+fn timestamp_types_into_datastore_entity_and_back() -> Result<(), Box<dyn Error>> {
+    let fixed_offset = DateTime::parse_from_rfc3339("2021-01-02T12:53:39.392+02:00")?;
+    let timestamps = Timestamps {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        prop_utc: fixed_offset.with_timezone(&Utc),
+        prop_fixed_offset: fixed_offset,
+        prop_date: NaiveDate::from_ymd_opt(2021, 1, 2).unwrap(),
+        prop_system_time: UNIX_EPOCH + Duration::from_millis(1_609_591_819_392),
+        prop_optional_utc: None,
+    };
+    let entity: DatastoreEntity = timestamps.clone().try_into()?;
+    let timestamps_are_back: Timestamps = entity.try_into()?;
+    assert_eq!(timestamps, timestamps_are_back);
+    // The offset is normalised to UTC
+    assert_eq!(
+        0,
+        timestamps_are_back
+            .prop_fixed_offset
+            .offset()
+            .local_minus_utc()
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "millisecond_timestamps"))]
+fn timestamp_types_serialize() -> Result<(), Box<dyn Error>> {
+    let fixed_offset = DateTime::parse_from_rfc3339("2021-01-02T12:53:39.392+02:00")?;
+    let serialized = fixed_offset.serialize()?;
+    let serialized_value: String =
+        get_datastore_value(&serialized, |d| d.0.timestamp_value.as_ref())?;
+    assert_eq!("2021-01-02T10:53:39.392000Z", serialized_value);
+
+    let serialized = NaiveDate::from_ymd_opt(2021, 1, 2).unwrap().serialize()?;
+    let serialized_value: String =
+        get_datastore_value(&serialized, |d| d.0.timestamp_value.as_ref())?;
+    assert_eq!("2021-01-02T00:00:00.000000Z", serialized_value);
+
+    let serialized = (UNIX_EPOCH + Duration::from_secs(1)).serialize()?;
+    let serialized_value: String =
+        get_datastore_value(&serialized, |d| d.0.timestamp_value.as_ref())?;
+    assert_eq!("1970-01-01T00:00:01.000000Z", serialized_value);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "millisecond_timestamps")]
+fn timestap_deserialize_serialize_millis() -> Result<(), Box<dyn Error>> {
+    let mut value_to_deserialize = DatastoreValue::empty();
+    value_to_deserialize.timestamp_value = Some("2021-01-02T12:53:39.392871Z".to_string());
+    let date_time = NaiveDateTime::deserialize(value_to_deserialize)?;
+    let serialized = date_time.serialize()?;
+    let serialized_value: String =
+        get_datastore_value(&serialized, |d| d.0.timestamp_value.as_ref())?;
+    assert_eq!("2021-01-02T12:53:39.392Z", serialized_value);
+    Ok(())
+}
+
+#[test]
+fn truncated_timestamps_into_datastore_entity_and_back() -> Result<(), Box<dyn Error>> {
+    let date_time = DateTime::parse_from_rfc3339("2021-01-02T12:53:39.392871Z")?;
+    let truncated = DateTime::parse_from_rfc3339("2021-01-02T12:53:39.392Z")?;
+    let timestamps = TruncatedTimestamps {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        prop_micros: date_time.naive_utc(),
+        prop_millis: date_time.naive_utc(),
+        prop_millis_array: vec![date_time.with_timezone(&Utc)],
+    };
+    let entity: DatastoreEntity = timestamps.try_into()?;
+    let timestamps_are_back: TruncatedTimestamps = entity.try_into()?;
+    #[cfg(not(feature = "millisecond_timestamps"))]
+    assert_eq!(date_time.naive_utc(), timestamps_are_back.prop_micros);
+    #[cfg(feature = "millisecond_timestamps")]
+    assert_eq!(truncated.naive_utc(), timestamps_are_back.prop_micros);
+    assert_eq!(truncated.naive_utc(), timestamps_are_back.prop_millis);
+    assert_eq!(
+        vec![truncated.with_timezone(&Utc)],
+        timestamps_are_back.prop_millis_array
+    );
+    Ok(())
+}
+
+#[test]
+fn geo_point_into_datastore_entity_and_back() -> Result<(), Box<dyn Error>> {
+    let location = GeoPoint::new(57.64911, 10.40744)?;
+    let place = Place {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        location,
+        location_hash: location.geohash(11),
+        previous_locations: vec![GeoPoint::new(-90.0, 180.0)?],
+    };
+    assert_eq!("u4pruydqqvj", place.location_hash);
+    let entity: DatastoreEntity = place.clone().try_into()?;
+    let place_is_back: Place = entity.try_into()?;
+    assert_eq!(place, place_is_back);
+    Ok(())
+}
+
+#[test]
+fn geo_point_out_of_range() {
+    assert_eq!(
+        Err(DatastoreGeoError::LatitudeOutOfRange(90.5)),
+        GeoPoint::new(90.5, 0.0)
+    );
+    assert_eq!(
+        Err(DatastoreSerializeError::GeoError(
+            DatastoreGeoError::LongitudeOutOfRange(-181.0)
+        )),
+        GeoPoint {
+            latitude: 0.0,
+            longitude: -181.0
+        }
+        .serialize()
+        .map(|_| ())
+    );
+
+    let mut value = DatastoreValue::empty();
+    value.geo_point_value = Some(LatLng {
+        latitude: Some(-91.0),
+        longitude: Some(0.0),
+    });
+    assert_eq!(
+        Err(DatastoreDeserializeError::GeoError(
+            DatastoreGeoError::LatitudeOutOfRange(-91.0)
+        )),
+        GeoPoint::deserialize(value)
+    );
+}
+
+#[test]
+fn maps_into_datastore_entity_and_back() -> Result<(), DatastorersError> {
+    let settings = Settings {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        labels: vec![("color".to_string(), "blue".to_string())]
+            .into_iter()
+            .collect(),
+        limits: vec![("max".to_string(), 10), ("min".to_string(), -10)]
+            .into_iter()
+            .collect(),
+        addresses: vec![(
+            "home".to_string(),
+            Address {
+                city: "Stockholm".to_string(),
+                street: None,
+                country: None,
+            },
+        )]
+        .into_iter()
+        .collect(),
+    };
+    let entity: Entity = DatastoreEntity::try_from(settings.clone())?.try_into()?;
+    let limits = entity.properties.as_ref().unwrap()["limits"]
+        .entity_value
+        .as_ref()
+        .unwrap();
+    assert_eq!(None, limits.key);
+    assert_eq!(
+        Some(10),
+        limits.properties.as_ref().unwrap()["max"].integer_value
+    );
+    let settings_are_back: Settings = DatastoreEntity::try_from(entity)?.try_into()?;
+    assert_eq!(settings, settings_are_back);
+    Ok(())
+}
+
+#[test]
+fn map_deserialize() {
+    let mut value = DatastoreValue::empty();
+    value.entity_value = Some(Entity {
+        key: None,
+        properties: None,
+    });
+    assert_eq!(
+        Ok(HashMap::new()),
+        HashMap::<String, i64>::deserialize(value)
+    );
+    assert_eq!(
+        Err(DatastoreDeserializeError::NoSuchValue),
+        HashMap::<String, i64>::deserialize(DatastoreValue::empty())
+    );
+    let mut value = DatastoreValue::empty();
+    value.entity_value = Some(Entity {
+        key: None,
+        properties: Some(
+            vec![("name".to_string(), 5.serialize().unwrap().unwrap().0)]
+                .into_iter()
+                .collect(),
+        ),
+    });
+    assert!(BTreeMap::<String, String>::deserialize(value).is_err());
+}
+
+#[test]
+fn enums_into_datastore_entity_and_back() -> Result<(), DatastorersError> {
+    let task = Task {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        status: Status::Closed,
+        priority: Some(Priority::Normal),
+        previous_statuses: vec![Status::Open],
+        assignees: vec![
+            Assignee::Nobody,
+            Assignee::Person {
+                name: "Alice".to_string(),
+                email: None,
+            },
+            Assignee::Team(5, "Platform".to_string()),
+        ],
+    };
+    let entity: DatastoreEntity = task.clone().try_into()?;
+    let task_is_back: Task = entity.try_into()?;
+    assert_eq!(task, task_is_back);
+    Ok(())
+}
+
+#[test]
+fn enums_serialize() -> Result<(), Box<dyn Error>> {
+    let serialized = Status::Closed.serialize()?;
+    let serialized_value: String = get_datastore_value(&serialized, |d| d.0.string_value.as_ref())?;
+    assert_eq!("closed", serialized_value);
+
+    let serialized = Priority::Low.serialize()?;
+    let serialized_value: i64 = get_datastore_value(&serialized, |d| d.0.integer_value.as_ref())?;
+    assert_eq!(-1, serialized_value);
+    let serialized = Priority::Normal.serialize()?;
+    let serialized_value: i64 = get_datastore_value(&serialized, |d| d.0.integer_value.as_ref())?;
+    assert_eq!(0, serialized_value);
+
+    let serialized = Assignee::Person {
+        name: "Alice".to_string(),
+        email: None,
+    }
+    .serialize()?;
+    let entity = get_datastore_value(&serialized, |d| d.0.entity_value.as_ref())?;
+    let properties = entity.properties.unwrap();
+    assert_eq!(Some("person".to_string()), properties["kind"].string_value);
+    assert_eq!(Some("Alice".to_string()), properties["Name"].string_value);
+    assert!(!properties.contains_key("email"));
+
+    let serialized = Assignee::Team(5, "Platform".to_string()).serialize()?;
+    let entity = get_datastore_value(&serialized, |d| d.0.entity_value.as_ref())?;
+    let properties = entity.properties.unwrap();
+    assert_eq!(Some("Team".to_string()), properties["kind"].string_value);
+    assert_eq!(Some(5), properties["0"].integer_value);
+    Ok(())
+}
+
+#[test]
+fn enums_unknown_variant() {
+    let mut value = DatastoreValue::empty();
+    value.string_value = Some("Closed".to_string());
+    assert_eq!(
+        Err(DatastoreDeserializeError::UnknownVariant {
+            enum_name: "Status",
+            value: "Closed".to_string()
+        }),
+        Status::deserialize(value)
+    );
+
+    let mut value = DatastoreValue::empty();
+    value.integer_value = Some(1);
+    assert_eq!(
+        Err(DatastoreDeserializeError::UnknownVariant {
+            enum_name: "Priority",
+            value: "1".to_string()
+        }),
+        Option::<Priority>::deserialize(value)
+    );
+
+    // Unit variants are not stored as embedded entities
+    let mut properties = DatastoreProperties::new();
+    properties.set_value("kind", "Nobody".to_string()).unwrap();
+    let value = properties.serialize().unwrap().unwrap();
+    assert_eq!(
+        Err(DatastoreDeserializeError::UnknownVariant {
+            enum_name: "Assignee",
+            value: "Nobody".to_string()
+        }),
+        Assignee::deserialize(value)
+    );
+    assert_eq!(
+        Ok(None),
+        Option::<Status>::deserialize(DatastoreValue::empty())
+    );
+}
+
+#[test]
+fn unindexed_properties() -> Result<(), DatastorersError> {
+    let article = Article {
+        key: IdentifierId::id(None, IdentifierNone::none()),
+        title: "Title".to_string(),
+        body: "Body".to_string(),
+        tags: vec!["a".to_string(), "b".to_string()],
+        summary: "s".repeat(1501),
+        thumbnail: Bytes(vec![0; 1501]),
+        revisions: vec![
+            Revision {
+                author: "Alice".to_string(),
+                diff: "+".to_string(),
+            },
+            Revision {
+                author: "a".repeat(1501),
+                diff: "+".to_string(),
+            },
+        ],
+    };
+    let entity: Entity = DatastoreEntity::try_from(article)?.try_into()?;
+    let properties = entity.properties.unwrap();
+    assert_eq!(None, properties["title"].exclude_from_indexes);
+    assert_eq!(Some(true), properties["body"].exclude_from_indexes);
+    // Arrays can not be excluded, the array values are excluded instead
+    assert_eq!(None, properties["tags"].exclude_from_indexes);
+    let tags = properties["tags"].array_value.as_ref().unwrap();
+    for tag in tags.values.as_ref().unwrap() {
+        assert_eq!(Some(true), tag.exclude_from_indexes);
+    }
+    // Long strings and blobs are excluded automatically when stored
+    assert_eq!(Some(true), properties["summary"].exclude_from_indexes);
+    assert_eq!(Some(true), properties["thumbnail"].exclude_from_indexes);
+
+    let revisions = properties["revisions"].array_value.as_ref().unwrap();
+    let revision = revisions.values.as_ref().unwrap()[0]
+        .entity_value
+        .as_ref()
+        .unwrap()
+        .properties
+        .as_ref()
+        .unwrap();
+    assert_eq!(None, revision["author"].exclude_from_indexes);
+    assert_eq!(Some(true), revision["diff"].exclude_from_indexes);
+    // Long values in embedded entities are excluded as well
+    let long_revision = revisions.values.as_ref().unwrap()[1]
+        .entity_value
+        .as_ref()
+        .unwrap()
+        .properties
+        .as_ref()
+        .unwrap();
+    assert_eq!(Some(true), long_revision["author"].exclude_from_indexes);
+
+    // Only values longer than 1500 bytes are excluded, also in arrays
+    let mut stored = DatastoreProperties::new();
+    stored.set("short", "s".repeat(1500))?;
+    stored.set("short_blob", Bytes(vec![0; 1500]))?;
+    stored.set("long_tags", vec!["s".repeat(1501), "s".to_string()])?;
+    let mut labels = BTreeMap::new();
+    labels.insert("long".to_string(), "s".repeat(1501));
+    labels.insert("short".to_string(), "s".to_string());
+    stored.set("labels", labels)?;
+    let stored = stored.into_map();
+    assert_eq!(None, stored["short"].exclude_from_indexes);
+    assert_eq!(None, stored["short_blob"].exclude_from_indexes);
+    let long_tags = stored["long_tags"].array_value.as_ref().unwrap();
+    let long_tags = long_tags.values.as_ref().unwrap();
+    assert_eq!(Some(true), long_tags[0].exclude_from_indexes);
+    assert_eq!(None, long_tags[1].exclude_from_indexes);
+    let labels = stored["labels"].entity_value.as_ref().unwrap();
+    let labels = labels.properties.as_ref().unwrap();
+    assert_eq!(Some(true), labels["long"].exclude_from_indexes);
+    assert_eq!(None, labels["short"].exclude_from_indexes);
+    // Values are not excluded when serialized for filters
+    assert_eq!(
+        None,
+        "s".repeat(1501).serialize()?.unwrap().exclude_from_indexes
+    );
+    Ok(())
+}
+
 fn get_datastore_value<T, F>(
     datastore_value: &Option<DatastoreValue>,
     select_prop: F,
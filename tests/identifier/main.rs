@@ -1,13 +1,15 @@
+use datastorers::deserialize::{DatastoreDeserializeError, Deserialize};
+use datastorers::serialize::{DatastoreSerializeError, Serialize};
 use datastorers::{
     id, name, DatastoreEntity, DatastoreKeyError, DatastoreManaged,
-    DatastoreNameRepresentationError, DatastoreProperties, DatastorersError,
+    DatastoreNameRepresentationError, DatastoreProperties, DatastoreValue, DatastorersError,
     DeserializeIdentifierName, IdentifierId, IdentifierName, IdentifierNone, IdentifierString,
-    KeyPath, KeyPathElement, Kind, SerializeIdentifierName,
+    KeyPath, KeyPathElement, Kind, ParentKeyPath, SerializeIdentifierName,
 };
 use google_datastore1::schemas::{Key, PathElement};
 use std::convert::TryInto;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct KindA {}
 
 impl Kind for KindA {
@@ -20,7 +22,7 @@ impl Kind for KindA {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct KindB {}
 
 impl Kind for KindB {
@@ -33,7 +35,7 @@ impl Kind for KindB {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct KindC {}
 
 impl Kind for KindC {
@@ -445,3 +447,97 @@ fn test_key_deserialization_with_representation() -> Result<(), DatastorersError
 
     Ok(())
 }
+
+fn parent_key<K>(parent: K::Parent) -> Vec<PathElement>
+where
+    K: ParentKeyPath,
+    K::Parent: KeyPath,
+{
+    parent.get_key().path.unwrap()
+}
+
+#[test]
+fn test_parent_key_path() {
+    let path = parent_key::<IdentifierId<KindA, IdentifierString<KindB>>>(id![1]);
+    assert_eq!(1, path.len());
+    assert_eq!(1, path[0].id.unwrap());
+    assert_eq!("a", path[0].kind.as_ref().unwrap());
+
+    let path = parent_key::<IdentifierString<KindA, IdentifierId<KindB>>>(name!["a"]);
+    assert_eq!(1, path.len());
+    assert_eq!("a", path[0].name.as_ref().unwrap());
+
+    // The parent of a key path with two ancestors is the two first elements
+    let path =
+        parent_key::<IdentifierId<KindA, IdentifierString<KindB, IdentifierId<KindC>>>>(id![
+            1,
+            name!["b"]
+        ]);
+    assert_eq!(2, path.len());
+    assert_eq!("b", path[1].kind.as_ref().unwrap());
+    assert_eq!("b", path[1].name.as_ref().unwrap());
+}
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "c"]
+struct Referencing {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub owner: IdentifierId<KindA>,
+    pub parent: Option<IdentifierString<KindB, IdentifierId<KindA>>>,
+    pub others: Vec<IdentifierString<KindB>>,
+}
+
+#[test]
+fn test_key_references() -> Result<(), DatastorersError> {
+    let referencing = Referencing {
+        key: id![1],
+        owner: id![2],
+        parent: Some(name!["b", id![3]]),
+        others: vec![name!["x"], name!["y"]],
+    };
+    let entity: DatastoreEntity = referencing.clone().try_into()?;
+    let properties: DatastoreProperties = entity.clone().try_into()?;
+    let owner = properties.into_map().remove("owner").unwrap();
+    assert_eq!(Some(referencing.owner.get_key()), owner.key_value);
+
+    let referencing_is_back: Referencing = entity.try_into()?;
+    assert!(referencing.owner == referencing_is_back.owner);
+    assert!(referencing.parent == referencing_is_back.parent);
+    assert!(referencing.others == referencing_is_back.others);
+    Ok(())
+}
+
+#[test]
+fn test_key_reference_validation() {
+    let key_value = |key: Key| {
+        let mut value = DatastoreValue::empty();
+        value.key_value = Some(key);
+        value
+    };
+    let wrong_kind: IdentifierId<KindB> = id![1];
+    assert_eq!(
+        DatastoreDeserializeError::KeyError(DatastoreKeyError::WrongKind {
+            expected: "a",
+            found: "b".to_string()
+        }),
+        IdentifierId::<KindA>::deserialize(key_value(wrong_kind.get_key())).unwrap_err()
+    );
+
+    // A child key is not a reference to the parent kind
+    let child: IdentifierId<KindA, IdentifierId<KindB>> = id![1, id![2]];
+    assert_eq!(
+        DatastoreDeserializeError::KeyError(DatastoreKeyError::WrongKeyPathLength {
+            expected: 1,
+            found: 2
+        }),
+        IdentifierId::<KindA>::deserialize(key_value(child.get_key())).unwrap_err()
+    );
+
+    // Incomplete keys can not be stored
+    let incomplete: IdentifierId<KindA> = id![None];
+    assert_eq!(
+        DatastoreSerializeError::IncompleteKey,
+        incomplete.serialize().unwrap_err()
+    );
+}
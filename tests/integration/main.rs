@@ -1,11 +1,16 @@
+use futures::TryStreamExt;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 
+use datastorers::cache::CachedConnection;
+use datastorers::deserialize::DatastoreDeserializeError;
+use datastorers::index::IndexYaml;
+use datastorers::metadata;
 use datastorers::transaction::TransactionConnection;
 use datastorers::DatastorersUpdatable;
 use datastorers::{
-    id, name, DatastoreClientError, DatastoreManaged, DatastoreParseError, DatastorersError,
-    DatastorersQueryable, IdentifierId, IdentifierNone, IdentifierString, Kind, Operator, Order,
+    gql, id, name, DatastoreClientError, DatastoreManaged, DatastorersError, DatastorersQueryable,
+    IdentifierId, IdentifierNone, IdentifierString, Kind, Operator, Order, ResultCollection,
 };
 
 use crate::connection::create_test_connection;
@@ -15,6 +20,7 @@ mod connection;
 #[derive(DatastoreManaged, Clone, Debug)]
 #[kind = "Test"]
 #[page_size = 2]
+#[index(properties(prop_string = "desc", prop_int = "desc"))]
 pub struct TestEntity {
     #[key]
     pub key: IdentifierId<Self>,
@@ -154,16 +160,23 @@ fn assert_client_error<T>(
     };
 }
 
-fn assert_parse_error<T>(result: Result<T, DatastorersError>, expected_error: DatastoreParseError) {
+fn assert_property_error<T>(
+    result: Result<T, DatastorersError>,
+    expected_property: &str,
+    expected_error: DatastoreDeserializeError,
+) {
     match result {
         Ok(_) => panic!("expect no entity to be found"),
         Err(e) => match e {
-            DatastorersError::ParseError(parse_error) => assert_eq!(
-                parse_error, expected_error,
-                "Expected error to be {}",
-                expected_error
-            ),
-            _ => panic!("Expected DatastoreParseError"),
+            DatastorersError::DatastorePropertyError(property_error) => {
+                assert_eq!(expected_property, property_error.property);
+                assert_eq!(
+                    property_error.source, expected_error,
+                    "Expected error to be {}",
+                    expected_error
+                )
+            }
+            _ => panic!("Expected DatastorePropertyError"),
         },
     };
 }
@@ -321,6 +334,59 @@ async fn test_get_collection_by_property() -> Result<(), DatastorersError> {
     Ok(())
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_get_previous_page() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    let common_string_prop = generate_random_string(15);
+    for prop_int in 0..5 {
+        let mut entity = generate_random_entity();
+        entity.prop_string = common_string_prop.clone();
+        entity.prop_int = prop_int;
+        entity.commit(&connection).await?;
+    }
+    let query = || {
+        TestEntity::query()
+            .filter(
+                TestEntity::PROP_STRING,
+                Operator::Equal,
+                common_string_prop.clone(),
+            )
+            .map(|query| query.order_by(TestEntity::PROP_INT, Order::Descending))
+    };
+    let int_props = |page: &ResultCollection<TestEntity>| -> Vec<i64> {
+        page.result.iter().map(|e| e.prop_int).collect()
+    };
+
+    let page_one = query()?.fetch(&connection).await?;
+    assert_eq!(vec![4, 3], int_props(&page_one));
+    let page_two = page_one.get_next_page(&connection).await?;
+    assert_eq!(vec![2, 1], int_props(&page_two));
+    let page_three = page_two.get_next_page(&connection).await?;
+    assert_eq!(vec![0], int_props(&page_three));
+
+    // Navigate back, results are in the original order
+    let page_two = page_three.get_previous_page(&connection).await?;
+    assert_eq!(vec![2, 1], int_props(&page_two));
+    assert!(page_two.has_more_results);
+    let page_one = page_two.get_previous_page(&connection).await?;
+    assert_eq!(vec![4, 3], int_props(&page_one));
+
+    // And forward again
+    let page_two = page_one.get_next_page(&connection).await?;
+    assert_eq!(vec![2, 1], int_props(&page_two));
+
+    // The first page has no previous page
+    let page_one = query()?.fetch(&connection).await?;
+    assert_client_error(
+        page_one.get_previous_page(&connection).await,
+        DatastoreClientError::NoMorePages,
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature = "integration_tests"), ignore)]
 async fn test_update_property() -> Result<(), DatastorersError> {
@@ -506,9 +572,10 @@ async fn test_optional_values() -> Result<(), DatastorersError> {
     assert_eq!(&fetched_entity.prop_string_array, &None);
 
     // Try fetch with the non optional type, shalll fail since not all values are set!
-    assert_parse_error(
+    assert_property_error(
         TestEntity::get_one_by_id(&connection, &id![inserted_id.id.unwrap()]).await,
-        DatastoreParseError::NoSuchValue,
+        "int_property",
+        DatastoreDeserializeError::NoSuchValue,
     );
     // Set the rest of the values
     let int_value = generate_random_int();
@@ -644,6 +711,62 @@ async fn test_transaction_with_update() -> Result<(), DatastorersError> {
     Ok(())
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_cached_connection() -> Result<(), DatastorersError> {
+    let connection = CachedConnection::new(create_test_connection().await);
+
+    let inserted = generate_random_entity().commit(&connection).await?;
+    let query = || {
+        TestEntity::query().filter(
+            TestEntity::PROP_STRING,
+            Operator::Equal,
+            inserted.prop_string.clone(),
+        )
+    };
+
+    // Lookups and queries are cached
+    let fetched = TestEntity::get_one_by_id(&connection, &inserted.key).await?;
+    assert_eq!(inserted.prop_int, fetched.prop_int);
+    let fetched = query()?.fetch_one(&connection).await?;
+    assert_eq!(inserted.prop_int, fetched.prop_int);
+    assert_eq!(2, connection.cache().len());
+    TestEntity::get_one_by_id(&connection, &inserted.key).await?;
+    assert_eq!(2, connection.cache().len());
+
+    // A commit invalidates both the lookup and the query of the kind
+    let mut updated = fetched;
+    updated.prop_int = generate_random_int();
+    let updated = updated.commit(&connection).await?;
+    assert!(connection.cache().is_empty());
+    let fetched = TestEntity::get_one_by_id(&connection, &inserted.key).await?;
+    assert_eq!(updated.prop_int, fetched.prop_int);
+
+    // Reads in a transaction are not served from, or stored in, the cache
+    let mut transaction = TransactionConnection::begin_transaction(&connection).await?;
+    let mut in_transaction = TestEntity::get_one_by_id(&transaction, &inserted.key).await?;
+    query()?.fetch_one(&transaction).await?;
+    assert_eq!(1, connection.cache().len());
+    in_transaction.prop_int = generate_random_int();
+    let prop_int = in_transaction.prop_int;
+    transaction.push_save(in_transaction)?;
+    transaction.commit().await?;
+
+    // The transaction commit invalidates the cache
+    assert!(connection.cache().is_empty());
+    let fetched = query()?.fetch_one(&connection).await?;
+    assert_eq!(prop_int, fetched.prop_int);
+
+    // Delete invalidates the cache
+    fetched.delete(&connection).await?;
+    assert!(connection.cache().is_empty());
+    assert!(TestEntity::get_one_by_id(&connection, &inserted.key)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature = "integration_tests"), ignore)]
 async fn test_name_key() -> Result<(), DatastorersError> {
@@ -755,6 +878,161 @@ async fn test_query_by_props() -> Result<(), DatastorersError> {
     Ok(())
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_query_by_typed_props() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    let mut entity = generate_random_entity();
+    entity.prop_string_array = vec![generate_random_string(10)];
+    let inserted = entity.commit(&connection).await?;
+
+    let fetched_entity = TestEntity::query()
+        .filter(
+            TestEntity::PROP_STRING,
+            Operator::Equal,
+            inserted.prop_string.clone(),
+        )?
+        .filter(TestEntity::PROP_INT, Operator::Equal, inserted.prop_int)?
+        .fetch_one(&connection)
+        .await?;
+    assert_eq!(&inserted.key, &fetched_entity.key);
+
+    // Array properties are filtered on their element type
+    let fetched_entity = TestEntity::query()
+        .filter(
+            TestEntity::PROP_STRING_ARRAY,
+            Operator::Equal,
+            inserted.prop_string_array[0].clone(),
+        )?
+        .fetch_one(&connection)
+        .await?;
+    assert_eq!(&inserted.key, &fetched_entity.key);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_query_key_range() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    // Save 3 entities with the same name, and order them by key
+    let name = generate_random_string(10);
+    let mut keys = vec![];
+    for _ in 0..3 {
+        let inserted = generate_entity_with_values(name.clone(), generate_random_int())
+            .commit(&connection)
+            .await?;
+        keys.push(inserted.key);
+    }
+    keys.sort_by_key(|key| key.id);
+
+    let fetched_keys: Vec<Option<i64>> = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name)?
+        .key_range(&keys[0], &keys[2])
+        .order_by_key(Order::Ascending)
+        .fetch(&connection)
+        .await?
+        .result
+        .into_iter()
+        .map(|e| e.key.id)
+        .collect();
+
+    // The end of the range is exclusive
+    assert_eq!(vec![keys[0].id, keys[1].id], fetched_keys);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_query_aggregation() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    // Save 3 entities with the same name, more than the page size of the entity
+    let name = generate_random_string(10);
+    for prop_int in &[1, 2, 6] {
+        generate_entity_with_values(name.clone(), *prop_int)
+            .commit(&connection)
+            .await?;
+    }
+
+    let count = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name.clone())?
+        .count(&connection)
+        .await?;
+    assert_eq!(3, count);
+
+    let sum = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name.clone())?
+        .sum(&connection, TestEntity::PROP_INT)
+        .await?;
+    assert_eq!(9, sum);
+
+    let avg = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name)?
+        .avg(&connection, TestEntity::PROP_INT)
+        .await?;
+    assert_eq!(Some(3.0), avg);
+
+    let avg = TestEntity::query()
+        .filter(
+            TestEntity::PROP_STRING,
+            Operator::Equal,
+            generate_random_string(10),
+        )?
+        .avg(&connection, TestEntity::PROP_INT)
+        .await?;
+    assert_eq!(None, avg);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_parallel_scan() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    let name = generate_random_string(10);
+    let mut inserted_keys = vec![];
+    for _ in 0..5 {
+        let inserted = generate_entity_with_values(name.clone(), generate_random_int())
+            .commit(&connection)
+            .await?;
+        inserted_keys.push(inserted.key.id);
+    }
+    inserted_keys.sort();
+
+    // Merged stream of all shards
+    let mut scanned_keys: Vec<Option<i64>> = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name.clone())?
+        .split_by_scatter(&connection, 4)
+        .await?
+        .into_stream(&connection)?
+        .map_ok(|e| e.key.id)
+        .try_collect()
+        .await?;
+    scanned_keys.sort();
+    assert_eq!(inserted_keys, scanned_keys);
+
+    // One stream per shard, split at the middle key
+    let split_point: IdentifierId<TestEntity> =
+        IdentifierId::id(inserted_keys[2], IdentifierNone::none());
+    let shard_streams = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, name)?
+        .split_at(&[split_point])
+        .into_shard_streams(&connection)?;
+    let mut shard_sizes = vec![];
+    for shard_stream in shard_streams {
+        let shard: Vec<TestEntity> = shard_stream.try_collect().await?;
+        shard_sizes.push(shard.len());
+    }
+    assert_eq!(vec![2, 3], shard_sizes);
+
+    Ok(())
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature = "integration_tests"), ignore)]
 async fn test_query_by_props_not_equal() -> Result<(), DatastorersError> {
@@ -1082,6 +1360,104 @@ async fn test_query_with_ancestor() -> Result<(), DatastorersError> {
     assert!(contains(&result_names, &child_b_a.name));
     assert!(contains(&result_names, &child_b_b.name));
 
+    // Children of the parent shall yield the same children
+    result_names = TestEntityChild::children_of(&parent_b.key)?
+        .fetch(&connection)
+        .await?
+        .result
+        .into_iter()
+        .map(|e| e.name)
+        .collect();
+    assert_eq!(result_names.len(), 2);
+    assert!(contains(&result_names, &child_b_a.name));
+    assert!(contains(&result_names, &child_b_b.name));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_gql_query() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+
+    // Create some entities with the same name, more than one page of them
+    let common_string_prop = generate_random_string(15);
+    let mut int_props = vec![];
+    for _ in 0..3 {
+        let mut entity = generate_random_entity();
+        entity.prop_string = common_string_prop.clone();
+        let inserted = entity.commit(&connection).await?;
+        int_props.push(inserted.prop_int);
+    }
+
+    // Named binding
+    let page = gql::<TestEntity>("SELECT * FROM Test WHERE Name = @name LIMIT 2")?
+        .bind("name", common_string_prop.clone())?
+        .fetch(&connection)
+        .await?;
+    assert_eq!(page.result.len(), 2);
+    assert!(page.has_more_results);
+    let mut fetched_int_props: Vec<i64> = page.result.iter().map(|e| e.prop_int).collect();
+
+    // Paging continues from the parsed gql query
+    let page = page.get_next_page(&connection).await?;
+    assert_eq!(page.result.len(), 1);
+    fetched_int_props.extend(page.result.iter().map(|e| e.prop_int));
+
+    fetched_int_props.sort_unstable();
+    int_props.sort_unstable();
+    assert_eq!(fetched_int_props, int_props);
+
+    // Positional bindings
+    let page = gql::<TestEntity>("SELECT * FROM Test WHERE Name = @1 AND int_property = @2")?
+        .bind_positional(common_string_prop.clone())?
+        .bind_positional(int_props[0])?
+        .fetch(&connection)
+        .await?;
+    assert_eq!(page.result.len(), 1);
+    assert_eq!(page.result[0].prop_int, int_props[0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_index_yaml_is_up_to_date() {
+    // index.yaml is deployed before the integration tests run, it must match the declared indexes
+    let index_yaml = IndexYaml::new()
+        .entity::<TestEntity>()
+        .entity::<TestEntityOptional>()
+        .entity::<TestEntityName>()
+        .entity::<TestEntityChild>()
+        .to_string();
+    assert_eq!(include_str!("index.yaml"), index_yaml);
+}
+
+#[tokio::test]
+#[cfg_attr(not(feature = "integration_tests"), ignore)]
+async fn test_metadata_queries() -> Result<(), DatastorersError> {
+    let connection = create_test_connection().await;
+    generate_random_entity().commit(&connection).await?;
+
+    let kinds = metadata::kinds(&connection).await?;
+    assert!(contains(&kinds, "Test"));
+
+    let properties = metadata::properties_of_kind(&connection, "Test").await?;
+    assert!(properties.iter().all(|p| p.kind == "Test"));
+    let name_property = properties
+        .iter()
+        .find(|p| p.name == "Name")
+        .expect("Name property not found");
+    assert!(contains(&name_property.representations, "STRING"));
+
+    let all_properties = metadata::properties(&connection).await?;
+    assert!(all_properties
+        .iter()
+        .any(|p| p.kind == "Test" && p.name == "int_property"));
+
+    // The test entities are stored in the default namespace
+    let namespaces = metadata::namespaces(&connection).await?;
+    assert!(contains(&namespaces, ""));
+
     Ok(())
 }
 
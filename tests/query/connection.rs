@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use datastorers::connection::DatastoreConnection;
+use google_api_auth::GetAccessToken;
+use google_datastore1::Client;
+
+use std::error::Error;
+
+//
+// Access tokens are never requested, the offline connection is never used to send requests
+//
+#[derive(Debug)]
+struct NoAuthentication;
+
+#[async_trait]
+impl GetAccessToken for NoAuthentication {
+    async fn access_token(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Err("offline connection".into())
+    }
+}
+
+//
+// A DatastoreConnection for tests that fail or complete before sending any request
+//
+pub struct OfflineConnection {
+    client: Client,
+}
+
+impl OfflineConnection {
+    pub fn new() -> OfflineConnection {
+        OfflineConnection {
+            client: Client::new(NoAuthentication),
+        }
+    }
+}
+
+impl DatastoreConnection for OfflineConnection {
+    fn get_client(&self) -> &Client {
+        &self.client
+    }
+
+    fn get_project_name(&self) -> String {
+        String::from("offline")
+    }
+}
@@ -0,0 +1,801 @@
+use datastorers::cache::CachedConnection;
+use datastorers::geo::{GeoBox, GeoPoint};
+use datastorers::index::{CompositeIndex, IndexCoverage, IndexYaml};
+use datastorers::{
+    gql, id, name, DatastoreClientError, DatastoreIndexError, DatastoreManaged, DatastorersError,
+    DatastorersQueryable, IdentifierId, IdentifierString, KeyPath, Operator, Order, QueryDisplay,
+    ResultCollection, Summable,
+};
+use google_datastore1::schemas::{
+    Entity, GqlQuery, Key, KindExpression, LookupResponse, Mutation, Projection, PropertyOrder,
+    PropertyOrderDirection, PropertyReference, Query, RunQueryRequest, RunQueryResponse,
+};
+use std::convert::TryInto;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::connection::OfflineConnection;
+
+mod connection;
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "TestChild"]
+pub struct TestEntityChild {
+    #[key]
+    pub key: IdentifierId<TestEntity, IdentifierString<Self>>,
+    pub owner: Option<IdentifierId<TestEntity>>,
+}
+
+#[derive(DatastoreManaged, Clone, Debug)]
+#[kind = "Test"]
+pub struct TestEntity {
+    #[key]
+    pub key: IdentifierId<Self>,
+
+    #[property = "Name"]
+    pub prop_string: String,
+
+    #[property = "int_property"]
+    pub prop_int: i64,
+}
+
+fn assert_client_error<T>(
+    result: Result<T, DatastorersError>,
+    expected_error: DatastoreClientError,
+) {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(DatastorersError::DatastoreClientError(client_error)) => {
+            assert_eq!(client_error, expected_error)
+        }
+        Err(e) => panic!("Unexpected err {:?}", e),
+    }
+}
+
+#[test]
+fn gql_kind_matches() -> Result<(), DatastorersError> {
+    gql::<TestEntity>("SELECT * FROM Test WHERE Name = @name")?;
+    gql::<TestEntity>("select __key__ from `Test` where int_property > @1")?;
+    gql::<TestEntity>("SELECT * FROM Test WHERE Name = 'FROM Other' LIMIT 5")?;
+    Ok(())
+}
+
+#[test]
+fn gql_kind_mismatch() {
+    assert_client_error(
+        gql::<TestEntity>("SELECT * FROM Other WHERE Name = @name"),
+        DatastoreClientError::GqlKindMismatch {
+            expected: "Test",
+            found: Some("Other".to_string()),
+        },
+    );
+    assert_client_error(
+        gql::<TestEntity>("SELECT * FROM `Test Two`"),
+        DatastoreClientError::GqlKindMismatch {
+            expected: "Test",
+            found: Some("Test Two".to_string()),
+        },
+    );
+    assert_client_error(
+        gql::<TestEntity>("SELECT *"),
+        DatastoreClientError::GqlKindMismatch {
+            expected: "Test",
+            found: None,
+        },
+    );
+}
+
+fn integration_index_yaml() -> IndexYaml {
+    include_str!("../integration/index.yaml")
+        .parse()
+        .expect("failed to parse index.yaml")
+}
+
+#[test]
+fn index_yaml_parse() -> Result<(), DatastoreIndexError> {
+    let index_yaml: IndexYaml = r#"
+# Comments are ignored
+indexes:
+- kind: Test
+  ancestor: yes
+  properties:
+  - name: 'Name' # asc by default
+  - name: "int_property"
+    direction: desc
+"#
+    .parse()?;
+    let expected = vec![CompositeIndex::new(
+        "Test",
+        true,
+        vec![
+            ("Name", Order::Ascending),
+            ("int_property", Order::Descending),
+        ],
+    )];
+    assert_eq!(expected, index_yaml.get_indexes());
+
+    let reparsed: IndexYaml = index_yaml.to_string().parse()?;
+    assert_eq!(expected, reparsed.get_indexes());
+    Ok(())
+}
+
+#[test]
+fn index_yaml_parse_error() {
+    let result = "indexes:\n- kind: Test\n  properties:\n  - name: Name\n    direction: up\n"
+        .parse::<IndexYaml>();
+    assert_eq!(
+        Err(DatastoreIndexError::InvalidIndexYaml {
+            line: 5,
+            reason: "direction must be asc or desc"
+        }),
+        result.map(|_| ())
+    );
+}
+
+#[test]
+fn index_coverage_built_in() -> Result<(), DatastorersError> {
+    let index_yaml = IndexYaml::new();
+    let queries: Vec<Query> = vec![
+        TestEntity::query().try_into()?,
+        TestEntity::query()
+            .filter(TestEntity::PROP_STRING, Operator::Equal, "a".to_string())?
+            .filter(TestEntity::PROP_INT, Operator::Equal, 1)?
+            .try_into()?,
+        TestEntity::query()
+            .filter(TestEntity::PROP_INT, Operator::GreaterThan, 1)?
+            .filter(TestEntity::PROP_INT, Operator::LessThan, 10)?
+            .order_by(TestEntity::PROP_INT, Order::Descending)
+            .try_into()?,
+        TestEntity::query()
+            .order_by(TestEntity::PROP_STRING, Order::Descending)
+            .try_into()?,
+        // Sort orders on properties with equality filters are ignored
+        TestEntity::query()
+            .filter(TestEntity::PROP_STRING, Operator::Equal, "a".to_string())?
+            .order_by(TestEntity::PROP_STRING, Order::Ascending)
+            .try_into()?,
+    ];
+    for query in queries {
+        assert_eq!(IndexCoverage::BuiltIn, index_yaml.coverage(&query));
+    }
+    Ok(())
+}
+
+#[test]
+fn index_coverage_composite() -> Result<(), DatastorersError> {
+    let index_yaml = integration_index_yaml();
+    let expected_index = CompositeIndex::new(
+        "Test",
+        false,
+        vec![
+            ("Name", Order::Descending),
+            ("int_property", Order::Descending),
+        ],
+    );
+
+    let query: Query = TestEntity::query()
+        .filter(
+            TestEntity::PROP_STRING,
+            Operator::GreaterThan,
+            "a".to_string(),
+        )?
+        .order_by(TestEntity::PROP_STRING, Order::Descending)
+        .order_by(TestEntity::PROP_INT, Order::Descending)
+        .try_into()?;
+    let coverage = index_yaml.coverage(&query);
+    assert!(coverage.is_covered());
+    assert!(coverage.needs_composite_index());
+    assert_eq!(IndexCoverage::Composite(expected_index.clone()), coverage);
+
+    // Indexes can be used in reverse order
+    let query: Query = TestEntity::query()
+        .order_by(TestEntity::PROP_STRING, Order::Ascending)
+        .order_by(TestEntity::PROP_INT, Order::Ascending)
+        .try_into()?;
+    assert_eq!(
+        IndexCoverage::Composite(expected_index),
+        index_yaml.coverage(&query)
+    );
+    Ok(())
+}
+
+#[test]
+fn index_coverage_missing() -> Result<(), DatastorersError> {
+    let index_yaml = integration_index_yaml();
+
+    let query: Query = TestEntity::query()
+        .order_by(TestEntity::PROP_STRING, Order::Ascending)
+        .order_by(TestEntity::PROP_INT, Order::Descending)
+        .try_into()?;
+    let coverage = index_yaml.coverage(&query);
+    assert!(!coverage.is_covered());
+    assert_eq!(
+        IndexCoverage::Missing(CompositeIndex::new(
+            "Test",
+            false,
+            vec![
+                ("Name", Order::Ascending),
+                ("int_property", Order::Descending)
+            ]
+        )),
+        coverage
+    );
+
+    // Equality filter properties come first in the missing index,
+    // followed by the inequality filter property
+    let query: Query = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, "a".to_string())?
+        .filter(TestEntity::PROP_INT, Operator::LessThan, 5)?
+        .try_into()?;
+    assert_eq!(
+        IndexCoverage::Missing(CompositeIndex::new(
+            "Test",
+            false,
+            vec![
+                ("Name", Order::Ascending),
+                ("int_property", Order::Ascending)
+            ]
+        )),
+        IndexYaml::new().coverage(&query)
+    );
+    // The direction of equality filter properties does not matter
+    assert!(index_yaml.coverage(&query).is_covered());
+    Ok(())
+}
+
+#[test]
+fn display_query() -> Result<(), DatastorersError> {
+    let parent: IdentifierId<TestEntity> = id![42];
+    let query = TestEntity::query()
+        .ancestor(&parent)?
+        .filter(TestEntity::PROP_STRING, Operator::Equal, "it's".to_string())?
+        .filter(TestEntity::PROP_INT, Operator::GreaterThanOrEqual, 5)?
+        .order_by(TestEntity::PROP_INT, Order::Ascending)
+        .limit(10);
+    assert_eq!(
+        "SELECT * FROM Test WHERE __key__ HAS ANCESTOR KEY(Test, 42) AND Name = 'it''s' \
+         AND int_property >= 5 ORDER BY int_property ASC LIMIT 10",
+        query.to_string()
+    );
+    assert_eq!(
+        "SELECT * FROM Test WHERE __key__ HAS ANCESTOR ? AND Name = ? \
+         AND int_property >= ? ORDER BY int_property ASC LIMIT 10",
+        query.display().redact_values().to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn display_raw_query() {
+    let query = Query {
+        kind: Some(vec![KindExpression {
+            name: Some("Test kind".to_string()),
+        }]),
+        projection: Some(vec![Projection {
+            property: Some(PropertyReference {
+                name: Some("__key__".to_string()),
+            }),
+        }]),
+        offset: Some(5),
+        start_cursor: Some("start".to_string()),
+        end_cursor: Some("end".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        "SELECT __key__ FROM `Test kind` OFFSET 5 START CURSOR 'start' END CURSOR 'end'",
+        QueryDisplay::new(&query).to_string()
+    );
+    assert_eq!(
+        "SELECT __key__ FROM `Test kind` OFFSET 5 START CURSOR ? END CURSOR ?",
+        QueryDisplay::new(&query).redact_values().to_string()
+    );
+}
+
+#[test]
+fn children_of_parent() -> Result<(), DatastorersError> {
+    let parent: IdentifierId<TestEntity> = id![42];
+    let query = TestEntityChild::children_of(&parent)?;
+    assert_eq!(
+        "SELECT * FROM TestChild WHERE __key__ HAS ANCESTOR KEY(Test, 42) LIMIT 50",
+        query.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn key_range_and_order() -> Result<(), DatastorersError> {
+    let start: IdentifierId<TestEntity> = id![10];
+    let end: IdentifierId<TestEntity> = id![20];
+    let query = TestEntity::query()
+        .key_range(&start, &end)
+        .order_by_key(Order::Descending);
+    assert_eq!(
+        "SELECT * FROM Test WHERE __key__ >= KEY(Test, 10) AND __key__ < KEY(Test, 20) \
+         ORDER BY __key__ DESC LIMIT 50",
+        query.to_string()
+    );
+
+    // A descending key order needs a composite index, an ascending does not
+    let index_yaml = IndexYaml::new();
+    let query: Query = TestEntity::query()
+        .key_range(&start, &end)
+        .order_by_key(Order::Ascending)
+        .try_into()?;
+    assert_eq!(IndexCoverage::BuiltIn, index_yaml.coverage(&query));
+    let query: Query = TestEntity::query()
+        .order_by_key(Order::Descending)
+        .try_into()?;
+    assert!(!index_yaml.coverage(&query).is_covered());
+    Ok(())
+}
+
+#[test]
+fn split_query_into_shards() -> Result<(), DatastorersError> {
+    let split_points: Vec<IdentifierId<TestEntity>> = vec![id![200], id![100]];
+    let shards: Vec<String> = TestEntity::query()
+        .filter(TestEntity::PROP_STRING, Operator::Equal, "a".to_string())?
+        .split_at(&split_points)
+        .shards()
+        .iter()
+        .map(|shard| shard.display().redact_values().to_string())
+        .collect();
+    assert_eq!(
+        vec![
+            "SELECT * FROM Test WHERE Name = ? AND __key__ < ? LIMIT 50",
+            "SELECT * FROM Test WHERE Name = ? AND __key__ >= ? AND __key__ < ? LIMIT 50",
+            "SELECT * FROM Test WHERE Name = ? AND __key__ >= ? LIMIT 50",
+        ],
+        shards
+    );
+
+    // Split points are sorted
+    let shard = TestEntity::query()
+        .split_at(&split_points)
+        .shards()
+        .remove(1);
+    assert_eq!(
+        "SELECT * FROM Test WHERE __key__ >= KEY(Test, 100) AND __key__ < KEY(Test, 200) LIMIT 50",
+        shard.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn split_query_sorted_by_property() -> Result<(), DatastorersError> {
+    let connection = OfflineConnection::new();
+    let split_points: Vec<IdentifierId<TestEntity>> = vec![id![100]];
+    let scan = TestEntity::query()
+        .order_by(TestEntity::PROP_INT, Order::Ascending)
+        .split_at(&split_points);
+    assert_client_error(
+        scan.into_stream(&connection),
+        DatastoreClientError::ScanOrderNotSupported,
+    );
+
+    // Sorting by key is supported
+    let scan = TestEntity::query()
+        .order_by_key(Order::Descending)
+        .split_at(&split_points);
+    assert!(scan.into_stream(&connection).is_ok());
+    Ok(())
+}
+
+#[test]
+fn split_points_in_key_order() {
+    // Ids are ordered by value, and duplicate split points are removed
+    let split_points: Vec<IdentifierId<TestEntity>> = vec![id![10], id![9], id![10], id![100]];
+    let shards: Vec<String> = TestEntity::query()
+        .split_at(&split_points)
+        .shards()
+        .iter()
+        .map(|shard| shard.to_string())
+        .collect();
+    assert_eq!(
+        vec![
+            "SELECT * FROM Test WHERE __key__ < KEY(Test, 9) LIMIT 50",
+            "SELECT * FROM Test WHERE __key__ >= KEY(Test, 9) AND __key__ < KEY(Test, 10) LIMIT 50",
+            "SELECT * FROM Test WHERE __key__ >= KEY(Test, 10) AND __key__ < KEY(Test, 100) LIMIT 50",
+            "SELECT * FROM Test WHERE __key__ >= KEY(Test, 100) LIMIT 50",
+        ],
+        shards
+    );
+}
+
+#[test]
+fn string_prefix_filter() -> Result<(), DatastorersError> {
+    let query = TestEntity::query().starts_with(TestEntity::PROP_STRING, "u4pr")?;
+    assert_eq!(
+        "SELECT * FROM Test WHERE Name >= 'u4pr' AND Name < 'u4pr\u{10ffff}' LIMIT 50",
+        query.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn geohash_cells() -> Result<(), DatastorersError> {
+    let cell = GeoBox::from_geohash("u4pruydqqvj")?;
+    assert!(cell.contains(&GeoPoint::new(57.64911, 10.40744)?));
+    assert!((cell.north_east.latitude - cell.south_west.latitude) < 0.001);
+    assert!(GeoBox::from_geohash("u4pa").is_err());
+
+    // A box within a single cell is covered by the prefix of that cell
+    let point = GeoPoint::new(57.64911, 10.40744)?;
+    let area = GeoBox::new(point, point)?;
+    assert_eq!(vec![point.geohash(12)], area.geohash_prefixes());
+
+    // A larger box is covered by at most 9 cells
+    let area = GeoBox::around(point, 2000.0);
+    let prefixes = area.geohash_prefixes();
+    assert!(prefixes.len() <= 9);
+    assert!(prefixes
+        .iter()
+        .all(|prefix| prefix.len() == prefixes[0].len()));
+    assert!(prefixes
+        .iter()
+        .any(|prefix| point.geohash(12).starts_with(prefix)));
+    // All corners of the box are in a covering cell
+    for corner in [
+        area.south_west,
+        area.north_east,
+        GeoPoint::new(area.south_west.latitude, area.north_east.longitude)?,
+        GeoPoint::new(area.north_east.latitude, area.south_west.longitude)?,
+    ]
+    .iter()
+    {
+        assert!(prefixes
+            .iter()
+            .any(|prefix| corner.geohash(12).starts_with(prefix)));
+    }
+    assert!(point.distance_meters(&area.north_east) > 2000.0);
+
+    // A box spanning a continent needs too many cells of precision 1, all geohashes match
+    let area = GeoBox::new(GeoPoint::new(-10.0, -100.0)?, GeoPoint::new(10.0, 100.0)?)?;
+    assert_eq!(vec![String::new()], area.geohash_prefixes());
+    let area = GeoBox::new(GeoPoint::new(-90.0, -180.0)?, GeoPoint::new(90.0, 180.0)?)?;
+    assert_eq!(vec![String::new()], area.geohash_prefixes());
+    let area = GeoBox::around(GeoPoint::new(89.0, 0.0)?, 500_000.0);
+    assert_eq!(vec![String::new()], area.geohash_prefixes());
+
+    // A box within one cell of precision 1 is covered by that cell
+    let area = GeoBox::new(GeoPoint::new(50.0, 0.0)?, GeoPoint::new(60.0, 40.0)?)?;
+    assert_eq!(vec!["u".to_string()], area.geohash_prefixes());
+    Ok(())
+}
+
+#[test]
+fn geohash_within_queries() -> Result<(), DatastorersError> {
+    let point = GeoPoint::new(57.64911, 10.40744)?;
+    let queries = TestEntity::query()
+        .geohash_within(TestEntity::PROP_STRING, &GeoBox::around(point, 2000.0))?
+        .into_iter()
+        .map(|query| query.display().redact_values().to_string())
+        .collect::<Vec<_>>();
+    assert!(!queries.is_empty());
+    for query in queries {
+        assert_eq!(
+            "SELECT * FROM Test WHERE Name >= ? AND Name < ? LIMIT 50",
+            query
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn key_reference_filter() -> Result<(), DatastorersError> {
+    let parent: IdentifierId<TestEntity> = id![7];
+    let query = TestEntityChild::query().filter(TestEntityChild::OWNER, Operator::Equal, parent)?;
+    assert_eq!(
+        "SELECT * FROM TestChild WHERE owner = KEY(Test, 7) LIMIT 50",
+        query.to_string()
+    );
+    Ok(())
+}
+
+fn result_page(query: Option<Query>) -> ResultCollection<TestEntity> {
+    ResultCollection {
+        result: vec![],
+        query,
+        end_cursor: Some("end".to_string()),
+        has_more_results: true,
+    }
+}
+
+fn order_directions(query: &Query) -> Vec<(String, PropertyOrderDirection)> {
+    query
+        .order
+        .iter()
+        .flatten()
+        .map(|order: &PropertyOrder| {
+            (
+                order.property.as_ref().unwrap().name.clone().unwrap(),
+                order.direction.unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn previous_page_query() -> Result<(), DatastorersError> {
+    let mut query: Query = TestEntity::query()
+        .order_by(TestEntity::PROP_INT, Order::Descending)
+        .order_by(TestEntity::PROP_STRING, Order::Ascending)
+        .try_into()?;
+    query.start_cursor = Some("start".to_string());
+    query.end_cursor = Some("end".to_string());
+    let previous = result_page(Some(query)).previous_page_query()?;
+    assert_eq!(
+        vec![
+            (
+                "int_property".to_string(),
+                PropertyOrderDirection::Ascending
+            ),
+            ("Name".to_string(), PropertyOrderDirection::Descending),
+            // Ties are ordered by key, the inverted query orders them by descending key
+            ("__key__".to_string(), PropertyOrderDirection::Descending),
+        ],
+        order_directions(&previous)
+    );
+    assert_eq!(Some("start".to_string()), previous.start_cursor);
+    assert_eq!(None, previous.end_cursor);
+
+    // An explicit key order is inverted, and not added again
+    let mut query: Query = TestEntity::query()
+        .order_by(TestEntity::PROP_INT, Order::Ascending)
+        .order_by_key(Order::Descending)
+        .try_into()?;
+    query.start_cursor = Some("start".to_string());
+    let previous = result_page(Some(query)).previous_page_query()?;
+    assert_eq!(
+        vec![
+            (
+                "int_property".to_string(),
+                PropertyOrderDirection::Descending
+            ),
+            ("__key__".to_string(), PropertyOrderDirection::Ascending),
+        ],
+        order_directions(&previous)
+    );
+
+    // A query without orders is ordered by key
+    let mut query: Query = TestEntity::query().try_into()?;
+    query.start_cursor = Some("start".to_string());
+    let previous = result_page(Some(query)).previous_page_query()?;
+    assert_eq!(
+        vec![("__key__".to_string(), PropertyOrderDirection::Descending)],
+        order_directions(&previous)
+    );
+
+    // The first page, and results without a query, have no previous page
+    let query: Query = TestEntity::query().try_into()?;
+    assert_client_error(
+        result_page(Some(query)).previous_page_query(),
+        DatastoreClientError::NoMorePages,
+    );
+    assert_client_error(
+        result_page(None).previous_page_query(),
+        DatastoreClientError::NoMorePages,
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn aggregate_with_limit() -> Result<(), DatastorersError> {
+    let connection = OfflineConnection::new();
+    let limited = || {
+        TestEntity::query()
+            .order_by(TestEntity::PROP_INT, Order::Descending)
+            .limit(10)
+    };
+    assert_client_error(
+        limited().count(&connection).await,
+        DatastoreClientError::AggregateWithLimit,
+    );
+    assert_client_error(
+        limited().sum(&connection, TestEntity::PROP_INT).await,
+        DatastoreClientError::AggregateWithLimit,
+    );
+    assert_client_error(
+        limited().avg(&connection, TestEntity::PROP_INT).await,
+        DatastoreClientError::AggregateWithLimit,
+    );
+    Ok(())
+}
+
+#[test]
+fn summable_values() {
+    assert_eq!(Some(5), Summable::checked_sum(2i64, 3));
+    assert_eq!(None, Summable::checked_sum(i64::MAX, 1));
+    assert_eq!(None, Summable::checked_sum(u8::MAX, 1));
+    assert_eq!(None, Summable::checked_sum(i32::MIN, -1));
+    assert_eq!(Some(1.5), Summable::checked_sum(1.0f64, 0.5));
+    assert_eq!(
+        Some(2.5),
+        Summable::checked_sum(2.0f32, 0.5).map(Summable::as_f64)
+    );
+}
+
+fn test_key(id: i64) -> Key {
+    let key: IdentifierId<TestEntity> = id![id];
+    key.get_key()
+}
+
+fn child_key(id: i64, name: &str) -> Key {
+    let key: IdentifierId<TestEntity, IdentifierString<TestEntityChild>> =
+        id![id, name![name.to_string()]];
+    key.get_key()
+}
+
+fn query_request(query: Query) -> RunQueryRequest {
+    RunQueryRequest {
+        query: Some(query),
+        ..Default::default()
+    }
+}
+
+fn gql_request(query_string: &str) -> RunQueryRequest {
+    RunQueryRequest {
+        gql_query: Some(GqlQuery {
+            query_string: Some(query_string.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn cache_expires_after_ttl() -> Result<(), DatastorersError> {
+    let connection = CachedConnection::new(OfflineConnection::new()).ttl(Duration::from_millis(50));
+    let cache = connection.cache();
+    let key = test_key(1);
+    let request = query_request(TestEntity::query().try_into()?);
+
+    cache.insert_lookup(vec![key.clone()], &LookupResponse::default());
+    cache.insert_query(&request, &RunQueryResponse::default());
+    assert!(cache.lookup(std::slice::from_ref(&key)).is_some());
+    assert!(cache.query(&request).is_some());
+    assert_eq!(2, cache.len());
+
+    sleep(Duration::from_millis(100));
+    assert!(cache.lookup(&[key]).is_none());
+    assert!(cache.query(&request).is_none());
+    assert!(cache.is_empty());
+    Ok(())
+}
+
+#[test]
+fn cache_evicts_oldest_entries() {
+    let connection = CachedConnection::new(OfflineConnection::new()).max_entries(2);
+    let cache = connection.cache();
+    let keys: Vec<Key> = (1..=3).map(test_key).collect();
+    for key in &keys {
+        cache.insert_lookup(vec![key.clone()], &LookupResponse::default());
+        sleep(Duration::from_millis(1));
+    }
+    assert_eq!(2, cache.len());
+    assert!(cache.lookup(&keys[0..1]).is_none());
+    assert!(cache.lookup(&keys[1..2]).is_some());
+    assert!(cache.lookup(&keys[2..3]).is_some());
+
+    // Responses with deferred keys are not cached
+    let deferred = LookupResponse {
+        deferred: Some(vec![keys[0].clone()]),
+        ..Default::default()
+    };
+    cache.insert_lookup(keys.clone(), &deferred);
+    assert!(cache.lookup(&keys).is_none());
+
+    // Nothing is cached without entries
+    let connection = CachedConnection::new(OfflineConnection::new()).max_entries(0);
+    connection
+        .cache()
+        .insert_lookup(vec![keys[0].clone()], &LookupResponse::default());
+    assert!(connection.cache().is_empty());
+}
+
+#[test]
+fn cache_invalidate_key() -> Result<(), DatastorersError> {
+    let connection = CachedConnection::new(OfflineConnection::new());
+    let cache = connection.cache();
+    let key_1 = test_key(1);
+    let key_2 = test_key(2);
+    let child_key = child_key(1, "a");
+    let test_query = query_request(TestEntity::query().try_into()?);
+    let child_query = query_request(TestEntityChild::query().try_into()?);
+    let gql_query = gql_request("SELECT * FROM Test");
+
+    cache.insert_lookup(vec![key_1.clone()], &LookupResponse::default());
+    cache.insert_lookup(vec![key_2.clone()], &LookupResponse::default());
+    cache.insert_query(&test_query, &RunQueryResponse::default());
+    cache.insert_query(&child_query, &RunQueryResponse::default());
+    cache.insert_query(&gql_query, &RunQueryResponse::default());
+
+    // Lookups of the key, queries of its kind and queries without a kind are removed
+    cache.invalidate_key(&key_1);
+    assert!(cache.lookup(&[key_1]).is_none());
+    assert!(cache.lookup(std::slice::from_ref(&key_2)).is_some());
+    assert!(cache.query(&test_query).is_none());
+    assert!(cache.query(&child_query).is_some());
+    assert!(cache.query(&gql_query).is_none());
+
+    // The kind of a key is the kind of its last path element
+    cache.invalidate_key(&child_key);
+    assert!(cache.query(&child_query).is_none());
+    assert!(cache.lookup(&[key_2]).is_some());
+    assert_eq!(1, cache.len());
+    Ok(())
+}
+
+#[test]
+fn cache_invalidate_kind() -> Result<(), DatastorersError> {
+    let connection = CachedConnection::new(OfflineConnection::new());
+    let cache = connection.cache();
+    let key = test_key(1);
+    let child_key = child_key(1, "a");
+    let test_query = query_request(TestEntity::query().try_into()?);
+    let child_query = query_request(TestEntityChild::query().try_into()?);
+    let gql_query = gql_request("SELECT * FROM TestChild");
+
+    cache.insert_lookup(vec![key.clone()], &LookupResponse::default());
+    cache.insert_lookup(vec![child_key.clone()], &LookupResponse::default());
+    cache.insert_query(&test_query, &RunQueryResponse::default());
+    cache.insert_query(&child_query, &RunQueryResponse::default());
+    cache.insert_query(&gql_query, &RunQueryResponse::default());
+
+    // Queries without a kind are always removed
+    cache.invalidate_kind("Test");
+    assert!(cache.lookup(&[key]).is_none());
+    assert!(cache.lookup(std::slice::from_ref(&child_key)).is_some());
+    assert!(cache.query(&test_query).is_none());
+    assert!(cache.query(&child_query).is_some());
+    assert!(cache.query(&gql_query).is_none());
+
+    cache.invalidate_kind("TestChild");
+    assert!(cache.is_empty());
+
+    cache.insert_lookup(vec![child_key], &LookupResponse::default());
+    cache.clear();
+    assert!(cache.is_empty());
+    Ok(())
+}
+
+#[test]
+fn cache_invalidate_mutations() {
+    let connection = CachedConnection::new(OfflineConnection::new());
+    let cache = connection.cache();
+    for id in 1..=5 {
+        cache.insert_lookup(vec![test_key(id)], &LookupResponse::default());
+    }
+    let entity = |id| Entity {
+        key: Some(test_key(id)),
+        properties: None,
+    };
+    let mutations = vec![
+        Mutation {
+            upsert: Some(entity(1)),
+            ..Default::default()
+        },
+        Mutation {
+            insert: Some(entity(2)),
+            ..Default::default()
+        },
+        Mutation {
+            update: Some(entity(3)),
+            ..Default::default()
+        },
+        Mutation {
+            delete: Some(test_key(4)),
+            ..Default::default()
+        },
+        // Inserted entities without a key are not known before the commit
+        Mutation {
+            insert: Some(Entity::default()),
+            ..Default::default()
+        },
+    ];
+    cache.invalidate_mutations(&mutations);
+    for id in 1..=4 {
+        assert!(cache.lookup(&[test_key(id)]).is_none());
+    }
+    assert!(cache.lookup(&[test_key(5)]).is_some());
+    assert_eq!(1, cache.len());
+}
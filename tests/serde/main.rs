@@ -0,0 +1,163 @@
+#![cfg(feature = "serde")]
+
+use datastorers::deserialize::{DatastoreDeserializeError, Deserialize};
+use datastorers::serde_value::{SerdeJson, SerdeValue};
+use datastorers::serialize::{DatastoreSerializeError, Serialize};
+use datastorers::{
+    DatastoreEntity, DatastoreManaged, DatastoreProperties, DatastorersError, IdentifierId,
+    IdentifierNone,
+};
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Document {
+    pub title: String,
+    pub revision: i64,
+    pub score: f64,
+    pub published: bool,
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+    pub counters: BTreeMap<String, i32>,
+    pub author: Author,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Author {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub enum Attachment {
+    Link(String),
+    Image { width: u32, height: u32 },
+    Removed,
+}
+
+#[derive(DatastoreManaged, Clone, Debug, PartialEq)]
+#[kind = "documents"]
+pub struct StoredDocument {
+    #[key]
+    pub key: IdentifierId<Self>,
+    pub native: SerdeValue<Document>,
+    pub json: SerdeJson<Document>,
+    pub optional: Option<SerdeValue<Author>>,
+}
+
+prop_compose! {
+    fn author()(name in ".*", email in proptest::option::of(".*")) -> Author {
+        Author { name, email }
+    }
+}
+
+fn attachment() -> impl Strategy<Value = Attachment> {
+    prop_oneof![
+        ".*".prop_map(Attachment::Link),
+        (any::<u32>(), any::<u32>())
+            .prop_map(|(width, height)| Attachment::Image { width, height }),
+        Just(Attachment::Removed),
+    ]
+}
+
+prop_compose! {
+    fn document()(
+        title in ".*",
+        revision in any::<i64>(),
+        // Quarters are represented exactly in both binary and decimal
+        score in any::<i32>().prop_map(|score| f64::from(score) / 4.0),
+        published in any::<bool>(),
+        summary in proptest::option::of(".*"),
+        tags in proptest::collection::vec(".*", 0..5),
+        counters in proptest::collection::btree_map(".*", any::<i32>(), 0..5),
+        author in author(),
+        attachments in proptest::collection::vec(attachment(), 0..5),
+    ) -> Document {
+        Document { title, revision, score, published, summary, tags, counters, author, attachments }
+    }
+}
+
+proptest! {
+    #[test]
+    fn serde_value_round_trip(document in document()) {
+        let value = SerdeValue(document.clone()).serialize().unwrap().unwrap();
+        prop_assert_eq!(SerdeValue(document), SerdeValue::deserialize(value).unwrap());
+    }
+
+    #[test]
+    fn serde_json_round_trip(document in document()) {
+        let value = SerdeJson(document.clone()).serialize().unwrap().unwrap();
+        prop_assert_eq!(Some(true), value.exclude_from_indexes);
+        prop_assert_eq!(SerdeJson(document), SerdeJson::deserialize(value).unwrap());
+    }
+
+    #[test]
+    fn serde_entity_round_trip(document in document(), author in proptest::option::of(author())) {
+        let stored = StoredDocument {
+            key: IdentifierId::id(None, IdentifierNone::none()),
+            native: SerdeValue(document.clone()),
+            json: SerdeJson(document),
+            optional: author.map(SerdeValue),
+        };
+        let entity: DatastoreEntity = stored.clone().try_into().unwrap();
+        let stored_is_back: StoredDocument = entity.try_into().unwrap();
+        prop_assert_eq!(stored, stored_is_back);
+    }
+}
+
+#[test]
+fn serde_value_native_tree() -> Result<(), DatastorersError> {
+    let author = Author {
+        name: "Alice".to_string(),
+        email: None,
+    };
+    let value = SerdeValue(author).serialize()?.unwrap();
+    let properties = value.0.entity_value.unwrap().properties.unwrap();
+    assert_eq!(Some("Alice".to_string()), properties["name"].string_value);
+    assert!(properties["email"].null_value.is_some());
+
+    let value = SerdeValue(vec![1, 2]).serialize()?.unwrap();
+    let values = value.0.array_value.unwrap().values.unwrap();
+    assert_eq!(Some(2), values[1].integer_value);
+    Ok(())
+}
+
+#[test]
+fn serde_value_long_values_unindexed() -> Result<(), DatastorersError> {
+    let author = Author {
+        name: "a".repeat(1501),
+        email: Some("alice@example.com".to_string()),
+    };
+    let mut stored = DatastoreProperties::new();
+    stored.set("author", SerdeValue(author))?;
+    let stored = stored.into_map();
+    let author = stored["author"].entity_value.as_ref().unwrap();
+    let properties = author.properties.as_ref().unwrap();
+    assert_eq!(Some(true), properties["name"].exclude_from_indexes);
+    assert_eq!(None, properties["email"].exclude_from_indexes);
+    Ok(())
+}
+
+#[test]
+fn serde_value_unsupported() {
+    assert_eq!(
+        Err(DatastoreSerializeError::IntegerOutOfRange { value: u64::MAX }),
+        SerdeValue(u64::MAX).serialize().map(|_| ())
+    );
+    assert!(matches!(
+        SerdeValue(vec![vec![1]]).serialize(),
+        Err(DatastoreSerializeError::SerdeError(_))
+    ));
+
+    let value = SerdeValue("text").serialize().unwrap().unwrap();
+    assert!(matches!(
+        SerdeValue::<Author>::deserialize(value.clone()),
+        Err(DatastoreDeserializeError::SerdeError(_))
+    ));
+    assert!(matches!(
+        SerdeJson::<Author>::deserialize(value),
+        Err(DatastoreDeserializeError::SerdeError(_))
+    ));
+}